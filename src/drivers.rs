@@ -1,2 +1,255 @@
+pub mod command;
+pub mod file;
+pub mod github;
+pub mod gitlab;
+pub mod http;
 pub mod issue;
+pub mod jira;
+pub mod linear;
 pub mod mock;
+pub mod notion;
+pub mod refreshing;
+
+use error_stack::{Report, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::{error::Error, fmt::Display};
+
+use crate::features::global_config::Credentials;
+use issue::Issue;
+
+// Errors that can occur while talking to an issue tracker
+#[derive(Debug)]
+pub enum DriverError {
+    MissingCredentials,
+    IssueNotFound(String),
+    UnknownDriver(String),
+    UnsupportedAccess,
+    Unauthorized,
+    Forbidden,
+    RequestFailed,
+    InvalidResponse,
+    InvalidFile,
+    CommandFailed,
+    UnsupportedOperation,
+    StatusNotFound(String),
+    DriverNotFound(String),
+}
+
+impl Display for DriverError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::MissingCredentials => fmt.write_str("Driver credentials not found"),
+            DriverError::IssueNotFound(id) => write!(fmt, "Issue {} not found", id),
+            DriverError::UnknownDriver(name) => write!(fmt, "Unknown driver {}", name),
+            DriverError::UnsupportedAccess => {
+                fmt.write_str("Access type not supported by the driver")
+            }
+            DriverError::Unauthorized => fmt.write_str("Issue tracker rejected the credentials"),
+            DriverError::Forbidden => fmt.write_str("Issue tracker denied the access"),
+            DriverError::RequestFailed => fmt.write_str("Issue tracker request failed"),
+            DriverError::InvalidResponse => fmt.write_str("Invalid issue tracker response"),
+            DriverError::InvalidFile => fmt.write_str("Invalid issues file"),
+            DriverError::CommandFailed => fmt.write_str("Issues command failed"),
+            DriverError::UnsupportedOperation => {
+                fmt.write_str("Operation not supported by the driver")
+            }
+            DriverError::StatusNotFound(status) => write!(fmt, "Status {} not found", status),
+            DriverError::DriverNotFound(url) => write!(fmt, "No driver known for {}", url),
+        }
+    }
+}
+
+impl Error for DriverError {}
+
+// What a driver is able to do, so callers can check before asking
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Capabilities {
+    pub get_issue: bool,
+    pub transition: bool,
+    pub assign: bool,
+}
+
+// Common interface between the issue trackers
+pub trait IssueDriver {
+    // List the issues of the board/project
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError>;
+    // Fetch a single issue by its id with all its details
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError>;
+    // Move an issue to the given status
+    fn transition_issue(&self, _id: &str, _status: &str) -> Result<(), DriverError> {
+        Err(Report::new(DriverError::UnsupportedOperation))
+    }
+    // Assign an issue to the authenticated user
+    fn assign_issue(&self, _id: &str) -> Result<(), DriverError> {
+        Err(Report::new(DriverError::UnsupportedOperation))
+    }
+    // Identity of the authenticated user, to check the credentials
+    fn whoami(&self) -> Result<String, DriverError> {
+        Err(Report::new(DriverError::UnsupportedOperation))
+    }
+    // Describe what the driver supports
+    fn capabilities(&self) -> Capabilities;
+}
+
+// The drivers that can be selected from the project configuration file
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub enum Driver {
+    #[default]
+    Mock,
+    Jira {
+        // JQL filter applied to the project issues, open issues by default
+        #[serde(default)]
+        jql: Option<String>,
+        // Jira Cloud or Server API, guessed from the URL when missing
+        #[serde(default)]
+        cloud: Option<bool>,
+    },
+    Github {
+        // Only list the issues assigned to the authenticated user
+        #[serde(default)]
+        assigned_to_me: bool,
+    },
+    Gitlab {
+        // Whether the identifier is a project or a group path
+        #[serde(default)]
+        scope: gitlab::Scope,
+        #[serde(default, flatten)]
+        filters: gitlab::Filters,
+    },
+    Notion {
+        #[serde(default)]
+        properties: notion::Properties,
+    },
+    Linear,
+    File {
+        // Path of the issues file, relative to the project directory
+        path: PathBuf,
+        // Guessed from the file extension when missing
+        #[serde(default)]
+        format: Option<file::Format>,
+    },
+    Command {
+        // Executable printing the JSON issue list, and its arguments
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl Driver {
+    // Lowercase name of the driver, the one parsed by from_str
+    pub fn name(&self) -> &'static str {
+        match self {
+            Driver::Mock => "mock",
+            Driver::Jira { .. } => "jira",
+            Driver::Github { .. } => "github",
+            Driver::Gitlab { .. } => "gitlab",
+            Driver::Notion { .. } => "notion",
+            Driver::Linear => "linear",
+            Driver::File { .. } => "file",
+            Driver::Command { .. } => "command",
+        }
+    }
+}
+
+impl FromStr for Driver {
+    type Err = Report<DriverError>;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "mock" => Ok(Driver::Mock),
+            "jira" => Ok(Driver::Jira {
+                jql: None,
+                cloud: None,
+            }),
+            "github" => Ok(Driver::Github {
+                assigned_to_me: false,
+            }),
+            "gitlab" => Ok(Driver::Gitlab {
+                scope: gitlab::Scope::default(),
+                filters: gitlab::Filters::default(),
+            }),
+            "notion" => Ok(Driver::Notion {
+                properties: notion::Properties::default(),
+            }),
+            "linear" => Ok(Driver::Linear),
+            "file" => Ok(Driver::File {
+                path: PathBuf::from("issues.json"),
+                format: None,
+            }),
+            "command" => Ok(Driver::Command {
+                command: String::from("gbc-issues"),
+                args: Vec::new(),
+            }),
+            _ => Err(Report::new(DriverError::UnknownDriver(name.to_string()))),
+        }
+    }
+}
+
+// Registry that builds the selected driver for the URL and identifier of a
+// project
+pub fn build(
+    driver: &Driver,
+    url: &str,
+    identifier: &str,
+    credentials: Option<&Credentials>,
+) -> Result<Box<dyn IssueDriver>, DriverError> {
+    Ok(match driver {
+        Driver::Mock => Box::new(mock::MockDriver),
+        Driver::Jira { jql, cloud } => Box::new(jira::JiraDriver::new(
+            url,
+            identifier,
+            jql.clone(),
+            *cloud,
+            required(credentials, url)?.access.clone(),
+        )),
+        Driver::Github { assigned_to_me } => Box::new(github::GithubDriver::new(
+            url,
+            identifier,
+            *assigned_to_me,
+            required(credentials, url)?.access.clone(),
+        )?),
+        Driver::Gitlab { scope, filters } => Box::new(gitlab::GitlabDriver::new(
+            url,
+            identifier,
+            *scope,
+            filters.clone(),
+            required(credentials, url)?.access.clone(),
+        )?),
+        Driver::Notion { properties } => Box::new(notion::NotionDriver::new(
+            url,
+            identifier,
+            properties.clone(),
+            required(credentials, url)?.access.clone(),
+        )?),
+        Driver::Linear => Box::new(linear::LinearDriver::new(
+            url,
+            identifier,
+            required(credentials, url)?.access.clone(),
+        )?),
+        Driver::File { path, format } => Box::new(file::FileDriver::new(path.clone(), *format)),
+        Driver::Command { command, args } => Box::new(command::CommandDriver::new(
+            command,
+            args,
+            identifier,
+            url,
+            credentials,
+        )),
+    })
+}
+
+// Drivers talking to a remote API can not work without credentials
+fn required<'a>(
+    credentials: Option<&'a Credentials>,
+    url: &str,
+) -> Result<&'a Credentials, DriverError> {
+    credentials.ok_or_else(|| {
+        Report::new(DriverError::MissingCredentials).attach_printable(format!(
+            "No credentials found for {}, please run 'gbc global-config add-credentials'",
+            url
+        ))
+    })
+}
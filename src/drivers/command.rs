@@ -0,0 +1,189 @@
+// Driver that gets the issues from an external executable, so any tracker can
+// be integrated without a built-in driver.
+//
+// The command is run in the project directory with the board identifier, the
+// project URL and the credentials in the GBC_* environment variables, and it
+// must print a JSON array of issues to its stdout:
+// [{ "id": "42", "title": "Fix the login page" }]
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use std::process::Command;
+
+use super::issue::Issue;
+use super::{Capabilities, DriverError, IssueDriver};
+use crate::features::global_config::{Access, Credentials};
+
+pub struct CommandDriver {
+    program: String,
+    args: Vec<String>,
+    identifier: String,
+    url: String,
+    access: Option<Access>,
+}
+
+impl CommandDriver {
+    pub fn new(
+        program: &str,
+        args: &[String],
+        identifier: &str,
+        url: &str,
+        credentials: Option<&Credentials>,
+    ) -> CommandDriver {
+        CommandDriver {
+            program: program.to_string(),
+            args: args.to_vec(),
+            identifier: identifier.to_string(),
+            url: url.to_string(),
+            access: credentials.map(|credentials| credentials.access.clone()),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env("GBC_IDENTIFIER", &self.identifier)
+            .env("GBC_URL", &self.url);
+
+        match &self.access {
+            Some(Access::Basic { username, password }) => {
+                command
+                    .env("GBC_USERNAME", username)
+                    .env("GBC_PASSWORD", password);
+            }
+            Some(
+                Access::Token { token }
+                | Access::OAuth {
+                    access_token: token,
+                    ..
+                },
+            ) => {
+                command.env("GBC_TOKEN", token);
+            }
+            _ => {}
+        }
+
+        command
+    }
+}
+
+impl IssueDriver for CommandDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let output = self
+            .command()
+            .output()
+            .into_report()
+            .change_context(DriverError::CommandFailed)
+            .attach_printable_lazy(|| format!("Could not run {}", self.program))?;
+
+        if !output.status.success() {
+            return Err(
+                Report::new(DriverError::CommandFailed).attach_printable(format!(
+                    "{} exited with {}: {}",
+                    self.program,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )),
+            );
+        }
+
+        serde_json::from_slice(&output.stdout)
+            .into_report()
+            .change_context(DriverError::InvalidResponse)
+            .attach_printable_lazy(|| format!("{} did not print a JSON issue list", self.program))
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        self.get_issues()?
+            .into_iter()
+            .find(|issue| issue.id == id)
+            .ok_or_else(|| Report::new(DriverError::IssueNotFound(id.to_string())))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_issue: false,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::SecretStore;
+
+    // Driver running a shell script
+    fn driver(script: &str, access: Option<Access>) -> CommandDriver {
+        let credentials = access.map(|access| Credentials {
+            url: String::from("https://tracker.example.com"),
+            access,
+            store: SecretStore::Plaintext,
+        });
+
+        CommandDriver::new(
+            "sh",
+            &[String::from("-c"), script.to_string()],
+            "ABC",
+            "https://tracker.example.com",
+            credentials.as_ref(),
+        )
+    }
+
+    // Prints the GBC_* variables in the issue titles
+    const ECHO_ENVIRONMENT: &str = r#"printf '[{"id": "%s", "title": "%s %s %s"}]' "$GBC_IDENTIFIER" "$GBC_URL" "${GBC_TOKEN:-no-token}" "${GBC_USERNAME:-no-username}:${GBC_PASSWORD:-no-password}""#;
+
+    #[test]
+    fn passes_the_project_and_the_token() {
+        let access = Access::Token {
+            token: String::from("hunter2"),
+        };
+
+        let issues = driver(ECHO_ENVIRONMENT, Some(access)).get_issues().unwrap();
+
+        assert_eq!(issues[0].id, "ABC");
+        assert_eq!(
+            issues[0].title,
+            "https://tracker.example.com hunter2 no-username:no-password"
+        );
+    }
+
+    #[test]
+    fn passes_the_username_and_password() {
+        let access = Access::Basic {
+            username: String::from("jdoe"),
+            password: String::from("hunter2"),
+        };
+
+        let issues = driver(ECHO_ENVIRONMENT, Some(access)).get_issues().unwrap();
+
+        assert_eq!(
+            issues[0].title,
+            "https://tracker.example.com no-token jdoe:hunter2"
+        );
+    }
+
+    #[test]
+    fn reports_a_failing_command_with_its_stderr() {
+        let error = driver("echo 'tracker is down' >&2; exit 2", None)
+            .get_issues()
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            DriverError::CommandFailed
+        ));
+        assert!(format!("{:?}", error).contains("tracker is down"));
+    }
+
+    #[test]
+    fn reports_an_output_that_is_not_json() {
+        let error = driver("echo 'no issues today'", None)
+            .get_issues()
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            DriverError::InvalidResponse
+        ));
+    }
+}
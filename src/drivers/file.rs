@@ -0,0 +1,233 @@
+// Driver that reads the issues from a local JSON, YAML or CSV file
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::issue::Issue;
+use super::{Capabilities, DriverError, IssueDriver};
+
+// Supported file formats, JSON array of issues, YAML list of issues or CSV
+// with id and title columns
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum Format {
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl Format {
+    // Guess the format from the file extension
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+// CSV cells are flat, empty cells are skipped and labels are separated by
+// semicolons
+fn csv_row_to_json(row: HashMap<String, String>) -> Value {
+    row.into_iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(column, value)| {
+            let value = match column.as_str() {
+                "labels" => value
+                    .split(';')
+                    .map(|label| label.trim())
+                    .filter(|label| !label.is_empty())
+                    .collect(),
+                _ => Value::String(value),
+            };
+
+            (column, value)
+        })
+        .collect()
+}
+
+pub struct FileDriver {
+    path: PathBuf,
+    format: Option<Format>,
+}
+
+impl FileDriver {
+    pub fn new(path: PathBuf, format: Option<Format>) -> FileDriver {
+        FileDriver { path, format }
+    }
+
+    fn parse(&self, content: &str, format: Format) -> Result<Vec<Issue>, DriverError> {
+        match format {
+            Format::Json => serde_json::from_str(content)
+                .into_report()
+                .change_context(DriverError::InvalidFile),
+            Format::Yaml => serde_yaml::from_str(content)
+                .into_report()
+                .change_context(DriverError::InvalidFile),
+            Format::Csv => csv::Reader::from_reader(content.as_bytes())
+                .deserialize::<HashMap<String, String>>()
+                .map(|row| {
+                    let row = row.into_report().change_context(DriverError::InvalidFile)?;
+                    serde_json::from_value(csv_row_to_json(row))
+                        .into_report()
+                        .change_context(DriverError::InvalidFile)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl IssueDriver for FileDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let format = self
+            .format
+            .or_else(|| Format::from_path(&self.path))
+            .ok_or_else(|| {
+                Report::new(DriverError::InvalidFile).attach_printable(format!(
+                    "Unknown format for {}, please set the file format",
+                    self.path.display()
+                ))
+            })?;
+        let content = std::fs::read_to_string(&self.path)
+            .into_report()
+            .change_context(DriverError::InvalidFile)
+            .attach_printable_lazy(|| format!("Could not read {}", self.path.display()))?;
+
+        self.parse(&content, format)
+            .attach_printable_lazy(|| format!("Could not parse {}", self.path.display()))
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        self.get_issues()?
+            .into_iter()
+            .find(|issue| issue.id == id)
+            .ok_or_else(|| Report::new(DriverError::IssueNotFound(id.to_string())))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_issue: false,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // Driver reading a file written in a temporary directory
+    fn driver(name: &str, content: &str, format: Option<Format>) -> (TempDir, FileDriver) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+
+        (dir, FileDriver::new(path, format))
+    }
+
+    fn summary(issues: Vec<Issue>) -> Vec<(String, String, Option<String>, Vec<String>)> {
+        issues
+            .into_iter()
+            .map(|issue| (issue.id, issue.title, issue.issue_type, issue.labels))
+            .collect()
+    }
+
+    fn expected() -> Vec<(String, String, Option<String>, Vec<String>)> {
+        vec![
+            (
+                String::from("42"),
+                String::from("Fix the login page"),
+                Some(String::from("Bug")),
+                vec![String::from("auth"), String::from("ui")],
+            ),
+            (String::from("43"), String::from("Add a logo"), None, vec![]),
+        ]
+    }
+
+    #[test]
+    fn reads_json() {
+        let (_dir, driver) = driver(
+            "issues.json",
+            r#"[
+                { "id": "42", "title": "Fix the login page", "type": "Bug", "labels": ["auth", "ui"] },
+                { "id": "43", "title": "Add a logo" }
+            ]"#,
+            None,
+        );
+
+        assert_eq!(summary(driver.get_issues().unwrap()), expected());
+    }
+
+    #[test]
+    fn reads_yaml() {
+        let content = "- id: \"42\"\n  title: Fix the login page\n  type: Bug\n  labels: [auth, ui]\n- id: \"43\"\n  title: Add a logo\n";
+        let (_dir, driver) = driver("issues.yml", content, None);
+
+        assert_eq!(summary(driver.get_issues().unwrap()), expected());
+    }
+
+    #[test]
+    fn reads_csv_labels_and_skips_empty_cells() {
+        let content = "id,title,type,labels,assignee\n42,Fix the login page,Bug,auth; ui;,\n43,Add a logo,,,\n";
+        let (_dir, driver) = driver("issues.csv", content, None);
+
+        let issues = driver.get_issues().unwrap();
+
+        assert_eq!(issues[1].assignee, None);
+        assert_eq!(summary(issues), expected());
+    }
+
+    #[test]
+    fn format_setting_wins_over_the_extension() {
+        let (_dir, driver) = driver(
+            "issues.txt",
+            "[{ \"id\": \"42\", \"title\": \"Fix\" }]",
+            Some(Format::Json),
+        );
+
+        assert_eq!(driver.get_issues().unwrap()[0].id, "42");
+        assert!(matches!(
+            Format::from_path(Path::new("issues.YAML")),
+            Some(Format::Yaml)
+        ));
+    }
+
+    #[test]
+    fn reports_an_unknown_extension() {
+        let (_dir, driver) = driver("issues.txt", "[]", None);
+
+        let error = driver.get_issues().unwrap_err();
+
+        assert!(matches!(error.current_context(), DriverError::InvalidFile));
+        assert!(format!("{:?}", error).contains("Unknown format"));
+    }
+
+    #[test]
+    fn reports_malformed_content() {
+        let files = [
+            ("issues.json", "[{ \"id\": 42 "),
+            ("issues.yaml", "- id: [\n"),
+            ("issues.csv", "title\nFix the login page\n"),
+        ];
+
+        for (name, content) in files {
+            let (_dir, driver) = driver(name, content, None);
+            let error = driver.get_issues().unwrap_err();
+
+            assert!(
+                matches!(error.current_context(), DriverError::InvalidFile),
+                "{}",
+                name
+            );
+            assert!(
+                format!("{:?}", error).contains("Could not parse"),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// GitHub Issues driver using the REST API
+use error_stack::{Report, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::issue::Issue;
+use super::{http, Capabilities, DriverError, IssueDriver};
+use crate::features::global_config::Access;
+
+const PAGE_SIZE: &str = "100";
+
+#[derive(Deserialize)]
+struct GithubIssue {
+    number: u64,
+    title: String,
+    body: Option<String>,
+    html_url: Option<String>,
+    state: Option<String>,
+    assignee: Option<GithubUser>,
+    user: Option<GithubUser>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    #[serde(default)]
+    labels: Vec<GithubLabel>,
+    // Issue type of the organization, when enabled
+    #[serde(rename = "type")]
+    issue_type: Option<GithubLabel>,
+    // Only present when the issue is a pull request
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct GithubLabel {
+    name: String,
+}
+
+impl From<GithubIssue> for Issue {
+    fn from(issue: GithubIssue) -> Self {
+        Issue {
+            id: issue.number.to_string(),
+            title: issue.title,
+            description: issue.body,
+            url: issue.html_url,
+            status: issue.state,
+            assignee: issue.assignee.map(|user| user.login),
+            reporter: issue.user.map(|user| user.login),
+            created: issue.created_at,
+            updated: issue.updated_at,
+            labels: issue.labels.into_iter().map(|label| label.name).collect(),
+            priority: None,
+            issue_type: issue.issue_type.map(|issue_type| issue_type.name),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GithubUser {
+    login: String,
+}
+
+pub struct GithubDriver {
+    // API base URL, https://api.github.com or https://{host}/api/v3 for GitHub Enterprise
+    url: String,
+    // Repository in the owner/repo form
+    repository: String,
+    assigned_to_me: bool,
+    token: String,
+}
+
+// Token of the access, the GitHub API only takes tokens
+pub fn token(access: &Access) -> Option<&str> {
+    match access {
+        Access::Token { token }
+        | Access::OAuth {
+            access_token: token,
+            ..
+        } => Some(token),
+        _ => None,
+    }
+}
+
+// Request to the GitHub API with its versioned media type and the token
+pub fn request(method: &str, url: &str, token: &str) -> ureq::Request {
+    ureq::request(method, url)
+        .set("Accept", "application/vnd.github+json")
+        .set("X-GitHub-Api-Version", "2022-11-28")
+        .set("Authorization", &format!("Bearer {}", token))
+}
+
+impl GithubDriver {
+    pub fn new(
+        url: &str,
+        repository: &str,
+        assigned_to_me: bool,
+        access: Access,
+    ) -> Result<GithubDriver, DriverError> {
+        let token = token(&access).ok_or_else(|| {
+            Report::new(DriverError::UnsupportedAccess)
+                .attach_printable("GitHub driver requires a token access")
+        })?;
+
+        Ok(GithubDriver {
+            url: url.trim_end_matches('/').to_string(),
+            repository: repository.to_string(),
+            assigned_to_me,
+            token: token.to_string(),
+        })
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        request(method, url, &self.token)
+    }
+
+    fn current_user(&self) -> Result<GithubUser, DriverError> {
+        let request = self.request("GET", &format!("{}/user", self.url));
+        http::read_json(http::send(request, None)?)
+    }
+}
+
+impl IssueDriver for GithubDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let mut request = self
+            .request(
+                "GET",
+                &format!("{}/repos/{}/issues", self.url, self.repository),
+            )
+            .query("state", "open")
+            .query("per_page", PAGE_SIZE);
+        if self.assigned_to_me {
+            request = request.query("assignee", &self.current_user()?.login);
+        }
+
+        let mut issues = Vec::new();
+        loop {
+            let response = http::send(request, None)?;
+            let next = http::next_link(&response);
+            let page: Vec<GithubIssue> = http::read_json(response)?;
+
+            issues.extend(
+                page.into_iter()
+                    .filter(|issue| issue.pull_request.is_none())
+                    .map(Issue::from),
+            );
+            match next {
+                Some(url) => request = self.request("GET", &url),
+                None => break,
+            }
+        }
+
+        Ok(issues)
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        let request = self.request(
+            "GET",
+            &format!("{}/repos/{}/issues/{}", self.url, self.repository, id),
+        );
+        let issue: GithubIssue = http::read_json(http::send(request, None)?)?;
+
+        match issue.pull_request {
+            Some(_) => Err(Report::new(DriverError::IssueNotFound(id.to_string()))
+                .attach_printable(format!("#{} is a pull request", id))),
+            None => Ok(issue.into()),
+        }
+    }
+
+    // GitHub issues have no status besides open and closed, only the
+    // assignment is supported
+    fn assign_issue(&self, id: &str) -> Result<(), DriverError> {
+        let request = self.request(
+            "POST",
+            &format!(
+                "{}/repos/{}/issues/{}/assignees",
+                self.url, self.repository, id
+            ),
+        );
+        let body = json!({ "assignees": [self.current_user()?.login] });
+        http::send(request, Some(&body))?;
+
+        Ok(())
+    }
+
+    fn whoami(&self) -> Result<String, DriverError> {
+        Ok(self.current_user()?.login)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_issue: true,
+            transition: false,
+            assign: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn driver(server: &Server, assigned_to_me: bool) -> GithubDriver {
+        GithubDriver::new(
+            &server.url(),
+            "owner/repo",
+            assigned_to_me,
+            Access::Token {
+                token: String::from("token"),
+            },
+        )
+        .unwrap()
+    }
+
+    fn issue(number: u64) -> serde_json::Value {
+        json!({ "number": number, "title": format!("Issue {}", number) })
+    }
+
+    fn pull_request(number: u64) -> serde_json::Value {
+        json!({ "number": number, "title": "Pull request", "pull_request": {} })
+    }
+
+    #[test]
+    fn issues_follow_the_link_header_and_skip_pull_requests() {
+        let mut server = Server::new();
+        let next = format!("{}/repositories/1/issues?page=2", server.url());
+        let first = server
+            .mock("GET", "/repos/owner/repo/issues")
+            .match_header("Authorization", "Bearer token")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("state".into(), "open".into()),
+                Matcher::UrlEncoded("per_page".into(), "100".into()),
+            ]))
+            .with_header("Link", &format!("<{}>; rel=\"next\"", next))
+            .with_body(json!([issue(3), pull_request(2)]).to_string())
+            .create();
+        let second = server
+            .mock("GET", "/repositories/1/issues")
+            .match_query(Matcher::UrlEncoded("page".into(), "2".into()))
+            .with_body(json!([issue(1)]).to_string())
+            .create();
+
+        let issues = driver(&server, false).get_issues().unwrap();
+
+        first.assert();
+        second.assert();
+        let ids = issues.into_iter().map(|issue| issue.id).collect::<Vec<_>>();
+        assert_eq!(ids, ["3", "1"]);
+    }
+
+    #[test]
+    fn issues_assigned_to_me_filter_on_the_login() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/user")
+            .with_body(json!({ "login": "octocat" }).to_string())
+            .create();
+        let issues = server
+            .mock("GET", "/repos/owner/repo/issues")
+            .match_query(Matcher::UrlEncoded("assignee".into(), "octocat".into()))
+            .with_body("[]")
+            .create();
+
+        driver(&server, true).get_issues().unwrap();
+
+        issues.assert();
+    }
+
+    #[test]
+    fn pull_request_is_not_an_issue() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/repos/owner/repo/issues/2")
+            .with_body(pull_request(2).to_string())
+            .create();
+
+        let error = driver(&server, false).get_issue("2").unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            DriverError::IssueNotFound(id) if id == "2"
+        ));
+    }
+
+    #[test]
+    fn basic_access_is_rejected() {
+        let access = Access::Basic {
+            username: String::from("user"),
+            password: String::from("password"),
+        };
+
+        let error = GithubDriver::new("https://api.github.com", "owner/repo", false, access);
+
+        assert!(matches!(
+            error.err().unwrap().current_context(),
+            DriverError::UnsupportedAccess
+        ));
+    }
+
+    #[test]
+    fn whoami_is_the_login() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/user")
+            .match_header("Authorization", "Bearer token")
+            .with_body(json!({ "login": "octocat" }).to_string())
+            .create();
+
+        assert_eq!(driver(&server, false).whoami().unwrap(), "octocat");
+    }
+
+    #[test]
+    fn whoami_reports_the_answer_of_a_rejected_token() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/user")
+            .with_status(401)
+            .with_body(r#"{ "message": "Bad credentials" }"#)
+            .create();
+
+        let error = driver(&server, false).whoami().unwrap_err();
+
+        assert!(matches!(error.current_context(), DriverError::Unauthorized));
+        assert!(
+            format!("{:?}", error).contains("answered 401: { \"message\": \"Bad credentials\" }")
+        );
+    }
+}
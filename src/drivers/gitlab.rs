@@ -0,0 +1,349 @@
+// GitLab Issues driver using the REST API v4
+use error_stack::{Report, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::issue::Issue;
+use super::{http, Capabilities, DriverError, IssueDriver};
+use crate::features::global_config::Access;
+
+const PAGE_SIZE: &str = "100";
+
+// Issues can be listed from a single project or from a whole group
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub enum Scope {
+    #[default]
+    Project,
+    Group,
+}
+
+// Filters applied to the listed issues
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Filters {
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub milestone: Option<String>,
+    // Username of the assignee
+    #[serde(default)]
+    pub assignee: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitlabIssue {
+    // Issue id inside its project, the one displayed as #iid
+    iid: u64,
+    title: String,
+    description: Option<String>,
+    web_url: Option<String>,
+    state: Option<String>,
+    assignee: Option<GitlabUser>,
+    author: Option<GitlabUser>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    issue_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitlabUser {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct GitlabCurrentUser {
+    id: u64,
+    username: String,
+}
+
+impl From<GitlabIssue> for Issue {
+    fn from(issue: GitlabIssue) -> Self {
+        Issue {
+            id: issue.iid.to_string(),
+            title: issue.title,
+            description: issue.description,
+            url: issue.web_url,
+            status: issue.state,
+            assignee: issue.assignee.map(|user| user.username),
+            reporter: issue.author.map(|user| user.username),
+            created: issue.created_at,
+            updated: issue.updated_at,
+            labels: issue.labels,
+            priority: None,
+            issue_type: issue.issue_type,
+        }
+    }
+}
+
+pub struct GitlabDriver {
+    // GitLab instance URL, https://gitlab.com or a self-hosted one
+    url: String,
+    // Project (group/project) or group (group/subgroup) path
+    path: String,
+    scope: Scope,
+    filters: Filters,
+    // Authorization header name and value
+    authorization: (&'static str, String),
+}
+
+// Authorization header of the access, OAuth tokens are Bearer tokens, the
+// personal access tokens have their own header
+pub fn authorization(access: &Access) -> Option<(&'static str, String)> {
+    match access {
+        Access::Token { token } => Some(("PRIVATE-TOKEN", token.clone())),
+        Access::OAuth { access_token, .. } => {
+            Some(("Authorization", format!("Bearer {}", access_token)))
+        }
+        _ => None,
+    }
+}
+
+impl GitlabDriver {
+    pub fn new(
+        url: &str,
+        path: &str,
+        scope: Scope,
+        filters: Filters,
+        access: Access,
+    ) -> Result<GitlabDriver, DriverError> {
+        let authorization = authorization(&access).ok_or_else(|| {
+            Report::new(DriverError::UnsupportedAccess)
+                .attach_printable("GitLab driver requires a token access")
+        })?;
+
+        Ok(GitlabDriver {
+            url: url.trim_end_matches('/').to_string(),
+            path: path.to_string(),
+            scope,
+            filters,
+            authorization,
+        })
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        ureq::request(method, url).set(self.authorization.0, &self.authorization.1)
+    }
+
+    fn current_user(&self) -> Result<GitlabCurrentUser, DriverError> {
+        let request = self.request("GET", &format!("{}/api/v4/user", self.url));
+        http::read_json(http::send(request, None)?)
+    }
+
+    fn update_issue(&self, id: &str, changes: serde_json::Value) -> Result<(), DriverError> {
+        if self.scope == Scope::Group {
+            return Err(Report::new(DriverError::UnsupportedOperation)
+                .attach_printable("Issue iids are not unique in a group"));
+        }
+
+        let request = self.request("PUT", &format!("{}/issues/{}", self.endpoint(), id));
+        http::send(request, Some(&changes))?;
+
+        Ok(())
+    }
+
+    // Project or group endpoint, the path is used url-encoded as the id
+    fn endpoint(&self) -> String {
+        let resource = match self.scope {
+            Scope::Project => "projects",
+            Scope::Group => "groups",
+        };
+
+        format!(
+            "{}/api/v4/{}/{}",
+            self.url,
+            resource,
+            self.path.replace('/', "%2F")
+        )
+    }
+}
+
+impl IssueDriver for GitlabDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let mut request = self
+            .request("GET", &format!("{}/issues", self.endpoint()))
+            .query("state", "opened")
+            .query("pagination", "keyset")
+            .query("order_by", "created_at")
+            .query("sort", "desc")
+            .query("per_page", PAGE_SIZE);
+        if !self.filters.labels.is_empty() {
+            request = request.query("labels", &self.filters.labels.join(","));
+        }
+        if let Some(milestone) = &self.filters.milestone {
+            request = request.query("milestone", milestone);
+        }
+        if let Some(assignee) = &self.filters.assignee {
+            request = request.query("assignee_username", assignee);
+        }
+
+        // Keyset and offset pagination both give the next page in the Link header
+        let mut issues = Vec::new();
+        loop {
+            let response = http::send(request, None)?;
+            let next = http::next_link(&response);
+            let page: Vec<GitlabIssue> = http::read_json(response)?;
+
+            issues.extend(page.into_iter().map(Issue::from));
+            match next {
+                Some(url) => request = self.request("GET", &url),
+                None => break,
+            }
+        }
+
+        Ok(issues)
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        if self.scope == Scope::Group {
+            return Err(Report::new(DriverError::IssueNotFound(id.to_string()))
+                .attach_printable("Issue iids are not unique in a group"));
+        }
+
+        let request = self.request("GET", &format!("{}/issues/{}", self.endpoint(), id));
+        let issue: GitlabIssue = http::read_json(http::send(request, None)?)?;
+
+        Ok(issue.into())
+    }
+
+    // GitLab issues have no workflow status, the status is added as a label
+    // (workflow::doing) like most GitLab boards do
+    fn transition_issue(&self, id: &str, status: &str) -> Result<(), DriverError> {
+        self.update_issue(id, json!({ "add_labels": status }))
+    }
+
+    fn assign_issue(&self, id: &str) -> Result<(), DriverError> {
+        let user = self.current_user()?;
+
+        self.update_issue(id, json!({ "assignee_ids": [user.id] }))
+    }
+
+    fn whoami(&self) -> Result<String, DriverError> {
+        Ok(self.current_user()?.username)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // Issue iids are only unique inside a project
+        let project = self.scope == Scope::Project;
+
+        Capabilities {
+            get_issue: project,
+            transition: project,
+            assign: project,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn driver(server: &Server, scope: Scope, filters: Filters) -> GitlabDriver {
+        GitlabDriver::new(
+            &server.url(),
+            "group/project",
+            scope,
+            filters,
+            Access::Token {
+                token: String::from("token"),
+            },
+        )
+        .unwrap()
+    }
+
+    fn issue(iid: u64) -> serde_json::Value {
+        json!({ "iid": iid, "title": format!("Issue {}", iid) })
+    }
+
+    #[test]
+    fn issues_follow_the_keyset_link_with_the_filters() {
+        let mut server = Server::new();
+        let next = format!(
+            "{}/api/v4/projects/group%2Fproject/issues?cursor=abc",
+            server.url()
+        );
+        let first = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/issues")
+            .match_header("PRIVATE-TOKEN", "token")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("pagination".into(), "keyset".into()),
+                Matcher::UrlEncoded("labels".into(), "bug,ui".into()),
+                Matcher::UrlEncoded("assignee_username".into(), "jdoe".into()),
+            ]))
+            .with_header("Link", &format!("<{}>; rel=\"next\"", next))
+            .with_body(json!([issue(7), issue(6)]).to_string())
+            .create();
+        let second = server
+            .mock("GET", "/api/v4/projects/group%2Fproject/issues")
+            .match_query(Matcher::UrlEncoded("cursor".into(), "abc".into()))
+            .with_body(json!([issue(5)]).to_string())
+            .create();
+        let filters = Filters {
+            labels: vec![String::from("bug"), String::from("ui")],
+            milestone: None,
+            assignee: Some(String::from("jdoe")),
+        };
+
+        let issues = driver(&server, Scope::Project, filters)
+            .get_issues()
+            .unwrap();
+
+        first.assert();
+        second.assert();
+        let ids = issues.into_iter().map(|issue| issue.id).collect::<Vec<_>>();
+        assert_eq!(ids, ["7", "6", "5"]);
+    }
+
+    #[test]
+    fn group_scope_lists_the_group_issues() {
+        let mut server = Server::new();
+        let issues = server
+            .mock("GET", "/api/v4/groups/group%2Fproject/issues")
+            .match_query(Matcher::Any)
+            .with_body(json!([issue(1)]).to_string())
+            .create();
+
+        let driver = driver(&server, Scope::Group, Filters::default());
+        driver.get_issues().unwrap();
+
+        issues.assert();
+        assert!(!driver.capabilities().transition);
+        assert!(matches!(
+            driver
+                .transition_issue("1", "workflow::doing")
+                .unwrap_err()
+                .current_context(),
+            DriverError::UnsupportedOperation
+        ));
+    }
+
+    #[test]
+    fn transition_adds_the_status_label() {
+        let mut server = Server::new();
+        let update = server
+            .mock("PUT", "/api/v4/projects/group%2Fproject/issues/3")
+            .match_body(Matcher::Json(json!({ "add_labels": "workflow::doing" })))
+            .create();
+
+        driver(&server, Scope::Project, Filters::default())
+            .transition_issue("3", "workflow::doing")
+            .unwrap();
+
+        update.assert();
+    }
+
+    #[test]
+    fn whoami_is_the_username() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/api/v4/user")
+            .match_header("PRIVATE-TOKEN", "token")
+            .with_body(json!({ "id": 1, "username": "ada" }).to_string())
+            .create();
+
+        let driver = driver(&server, Scope::Project, Filters::default());
+
+        assert_eq!(driver.whoami().unwrap(), "ada");
+    }
+}
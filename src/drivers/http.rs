@@ -0,0 +1,131 @@
+// HTTP helpers shared by the code talking to a remote API
+use base64::{engine::general_purpose::STANDARD, Engine};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use serde::de::DeserializeOwned;
+
+use super::DriverError;
+use crate::features::global_config::Access;
+
+// Add the authorization header matching the access type, Basic Auth or Bearer
+pub fn authorize(request: ureq::Request, access: &Access) -> ureq::Request {
+    match access {
+        Access::Basic { username, password } => {
+            let encoded = STANDARD.encode(format!("{}:{}", username, password));
+            request.set("Authorization", &format!("Basic {}", encoded))
+        }
+        Access::Token { token }
+        | Access::OAuth {
+            access_token: token,
+            ..
+        } => request.set("Authorization", &format!("Bearer {}", token)),
+        // The external sources are resolved with the credentials
+        _ => request,
+    }
+}
+
+// Send the request, with an optional JSON body, and turn the HTTP failures
+// into driver errors
+pub fn send(
+    request: ureq::Request,
+    body: Option<&serde_json::Value>,
+) -> Result<ureq::Response, DriverError> {
+    let url = request.url().to_string();
+    let response = match body {
+        Some(body) => request.send_json(body),
+        None => request.call(),
+    };
+
+    response.map_err(|error| match error {
+        ureq::Error::Status(code, response) => {
+            // Only a 401 means the credentials are not valid, a 403 is a rate
+            // limit, a missing scope or permission
+            let context = match code {
+                401 => DriverError::Unauthorized,
+                403 => DriverError::Forbidden,
+                _ => DriverError::RequestFailed,
+            };
+            Report::new(context).attach_printable(format!(
+                "{} answered {}: {}",
+                url,
+                code,
+                response.into_string().unwrap_or_default()
+            ))
+        }
+        ureq::Error::Transport(transport) => {
+            Report::new(DriverError::RequestFailed).attach_printable(transport.to_string())
+        }
+    })
+}
+
+// Parse the JSON body of a response
+pub fn read_json<T: DeserializeOwned>(response: ureq::Response) -> Result<T, DriverError> {
+    let url = response.get_url().to_string();
+
+    response
+        .into_json()
+        .into_report()
+        .change_context(DriverError::InvalidResponse)
+        .attach_printable_lazy(|| format!("Unexpected response from {}", url))
+}
+
+// Next page URL from a `Link: <url>; rel="next"` pagination header
+pub fn next_link(response: &ureq::Response) -> Option<String> {
+    response.header("Link")?.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(link: &str) -> ureq::Response {
+        format!("HTTP/1.1 200 OK\r\nLink: {}\r\n\r\n", link)
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn next_link_picks_the_next_relation() {
+        let response = response(
+            "<https://api.github.com/repositories/1/issues?page=1>; rel=\"prev\", \
+             <https://api.github.com/repositories/1/issues?page=3>; rel=\"next\", \
+             <https://api.github.com/repositories/1/issues?page=9>; rel=\"last\"",
+        );
+
+        assert_eq!(
+            next_link(&response).as_deref(),
+            Some("https://api.github.com/repositories/1/issues?page=3")
+        );
+    }
+
+    #[test]
+    fn next_link_reads_extra_parameters() {
+        let response = response(
+            "<https://gitlab.example.com/api/v4/issues?page=2>; type=\"text/html\"; rel=\"next\"",
+        );
+
+        assert_eq!(
+            next_link(&response).as_deref(),
+            Some("https://gitlab.example.com/api/v4/issues?page=2")
+        );
+    }
+
+    #[test]
+    fn next_link_is_none_on_the_last_page() {
+        let last = response("<https://api.github.com/issues?page=1>; rel=\"first\"");
+        let missing: ureq::Response = "HTTP/1.1 200 OK\r\n\r\n".parse().unwrap();
+
+        assert_eq!(next_link(&last), None);
+        assert_eq!(next_link(&missing), None);
+    }
+}
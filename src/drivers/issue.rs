@@ -1,13 +1,70 @@
+use serde::{Deserialize, Serialize};
+
 // Struck that represents a common issue interface between Drivers
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Issue {
     pub id: String,
     pub title: String,
-    // Add this extra fields ASA we add support for real drivers
-    // pub description: Option<String>,
-    // pub url: Option<String>,
-    // pub status: Option<String>,
-    // pub assignee: Option<String>,
-    // pub reporter: Option<String>,
-    // pub created: Option<String>,
-    // pub updated: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    // Link to the issue in the tracker web interface
+    #[serde(default)]
+    pub url: Option<String>,
+    // Workflow state name, "In Progress"
+    #[serde(default)]
+    pub status: Option<String>,
+    // Display name of the assignee
+    #[serde(default)]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub reporter: Option<String>,
+    // RFC 3339 timestamps as returned by the trackers
+    #[serde(default)]
+    pub created: Option<String>,
+    #[serde(default)]
+    pub updated: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub priority: Option<String>,
+    // Bug, Story, Task...
+    #[serde(default, alias = "type")]
+    pub issue_type: Option<String>,
+}
+
+impl Issue {
+    // Name of the fields that can be used in the branch templates
+    pub const FIELDS: [&'static str; 12] = [
+        "id",
+        "title",
+        "description",
+        "url",
+        "status",
+        "assignee",
+        "reporter",
+        "created",
+        "updated",
+        "labels",
+        "priority",
+        "issue_type",
+    ];
+
+    // Value of a field by its name, labels are joined by commas
+    pub fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id.clone()),
+            "title" => Some(self.title.clone()),
+            "description" => self.description.clone(),
+            "url" => self.url.clone(),
+            "status" => self.status.clone(),
+            "assignee" => self.assignee.clone(),
+            "reporter" => self.reporter.clone(),
+            "created" => self.created.clone(),
+            "updated" => self.updated.clone(),
+            "labels" => Some(self.labels.join(",")).filter(|labels| !labels.is_empty()),
+            "priority" => self.priority.clone(),
+            "issue_type" => self.issue_type.clone(),
+            _ => None,
+        }
+    }
 }
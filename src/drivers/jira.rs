@@ -0,0 +1,517 @@
+// Jira Cloud/Server driver using the REST API v2
+use error_stack::{Report, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::issue::Issue;
+use super::{http, Capabilities, DriverError, IssueDriver};
+use crate::features::global_config::Access;
+
+const PAGE_SIZE: &str = "50";
+const FIELDS: &str =
+    "summary,description,status,assignee,reporter,created,updated,labels,priority,issuetype";
+const DEFAULT_JQL: &str = "statusCategory != Done ORDER BY updated DESC";
+
+// Page of issues returned by the search endpoints, Jira Server paginates with
+// startAt/total while Jira Cloud uses a nextPageToken
+#[derive(Deserialize)]
+struct SearchPage {
+    issues: Vec<JiraIssue>,
+    total: Option<usize>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JiraIssue {
+    key: String,
+    fields: JiraFields,
+}
+
+#[derive(Deserialize)]
+struct JiraFields {
+    summary: String,
+    description: Option<String>,
+    status: Option<Named>,
+    assignee: Option<JiraUser>,
+    reporter: Option<JiraUser>,
+    created: Option<String>,
+    updated: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    priority: Option<Named>,
+    issuetype: Option<Named>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct JiraUser {
+    #[serde(rename = "displayName")]
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Deserialize)]
+struct Transition {
+    id: String,
+    name: String,
+    to: Named,
+}
+
+// Authenticated user, Jira Cloud identifies users by account id and Jira
+// Server by name
+#[derive(Deserialize)]
+struct Myself {
+    #[serde(rename = "accountId")]
+    account_id: Option<String>,
+    name: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+}
+
+impl JiraIssue {
+    fn into_issue(self, url: &str) -> Issue {
+        let fields = self.fields;
+
+        Issue {
+            url: Some(format!("{}/browse/{}", url, self.key)),
+            id: self.key,
+            title: fields.summary,
+            description: fields.description,
+            status: fields.status.map(|status| status.name),
+            assignee: fields.assignee.map(|user| user.display_name),
+            reporter: fields.reporter.map(|user| user.display_name),
+            created: fields.created,
+            updated: fields.updated,
+            labels: fields.labels,
+            priority: fields.priority.map(|priority| priority.name),
+            issue_type: fields.issuetype.map(|issue_type| issue_type.name),
+        }
+    }
+}
+
+pub struct JiraDriver {
+    url: String,
+    // Project key (ABC) or numeric board id (42)
+    identifier: String,
+    jql: Option<String>,
+    cloud: bool,
+    access: Access,
+}
+
+impl JiraDriver {
+    pub fn new(
+        url: &str,
+        identifier: &str,
+        jql: Option<String>,
+        cloud: Option<bool>,
+        access: Access,
+    ) -> JiraDriver {
+        JiraDriver {
+            url: url.trim_end_matches('/').to_string(),
+            identifier: identifier.to_string(),
+            jql,
+            // Cloud sites are reached directly or, with OAuth tokens, through
+            // api.atlassian.com/ex/jira/{cloud id}
+            cloud: cloud.unwrap_or_else(|| {
+                url.contains(".atlassian.net") || url.contains("api.atlassian.com")
+            }),
+            access,
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = ureq::request(method, &format!("{}{}", self.url, path))
+            .set("Accept", "application/json");
+        http::authorize(request, &self.access)
+    }
+
+    fn myself(&self) -> Result<Myself, DriverError> {
+        http::read_json(http::send(self.request("GET", "/rest/api/2/myself"), None)?)
+    }
+
+    fn is_board(&self) -> bool {
+        !self.identifier.is_empty() && self.identifier.chars().all(|c| c.is_ascii_digit())
+    }
+
+    fn search_path(&self) -> String {
+        if self.is_board() {
+            format!("/rest/agile/1.0/board/{}/issue", self.identifier)
+        } else if self.cloud {
+            String::from("/rest/api/2/search/jql")
+        } else {
+            String::from("/rest/api/2/search")
+        }
+    }
+
+    // Restrict the configured JQL to the project, keeping its ORDER BY clause
+    // at the end of the query
+    fn jql(&self) -> String {
+        let jql = self.jql.as_deref().unwrap_or(DEFAULT_JQL).trim();
+        if self.is_board() {
+            return jql.to_string();
+        }
+
+        let (filter, order) = match order_by_index(jql) {
+            Some(index) => (jql[..index].trim(), &jql[index..]),
+            None => (jql, ""),
+        };
+        let project = format!("project = \"{}\"", self.identifier);
+
+        let jql = if filter.is_empty() {
+            format!("{} {}", project, order)
+        } else {
+            format!("{} AND ({}) {}", project, filter, order)
+        };
+
+        jql.trim().to_string()
+    }
+}
+
+// Start of the ORDER BY clause, the quoted values can contain the words
+fn order_by_index(jql: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (index, c) in jql.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => {
+                let clause = jql
+                    .get(index..index + "order by".len())
+                    .is_some_and(|words| words.eq_ignore_ascii_case("order by"));
+                if clause {
+                    return Some(index);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+impl IssueDriver for JiraDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let jql = self.jql();
+        let mut issues = Vec::new();
+        let mut next_page_token: Option<String> = None;
+
+        loop {
+            let request = self
+                .request("GET", &self.search_path())
+                .query("jql", &jql)
+                .query("fields", FIELDS)
+                .query("maxResults", PAGE_SIZE);
+            let request = match &next_page_token {
+                Some(token) => request.query("nextPageToken", token),
+                None => request.query("startAt", &issues.len().to_string()),
+            };
+            let page: SearchPage = http::read_json(http::send(request, None)?)?;
+            let count = page.issues.len();
+
+            issues.extend(
+                page.issues
+                    .into_iter()
+                    .map(|issue| issue.into_issue(&self.url)),
+            );
+            match (page.next_page_token, page.total) {
+                (Some(token), _) => next_page_token = Some(token),
+                (None, Some(total)) if count > 0 && issues.len() < total => {}
+                _ => break,
+            }
+        }
+
+        Ok(issues)
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        let request = self
+            .request("GET", &format!("/rest/api/2/issue/{}", id))
+            .query("fields", FIELDS);
+        let issue: JiraIssue = http::read_json(http::send(request, None)?)?;
+
+        Ok(issue.into_issue(&self.url))
+    }
+
+    // The status is the name of a transition or of its target status
+    fn transition_issue(&self, id: &str, status: &str) -> Result<(), DriverError> {
+        let path = format!("/rest/api/2/issue/{}/transitions", id);
+        let transitions: Transitions =
+            http::read_json(http::send(self.request("GET", &path), None)?)?;
+        let available = transitions
+            .transitions
+            .iter()
+            .map(|transition| transition.to.name.clone())
+            .collect::<Vec<_>>();
+
+        let transition = transitions
+            .transitions
+            .into_iter()
+            .find(|transition| {
+                transition.name.eq_ignore_ascii_case(status)
+                    || transition.to.name.eq_ignore_ascii_case(status)
+            })
+            .ok_or_else(|| {
+                Report::new(DriverError::StatusNotFound(status.to_string())).attach_printable(
+                    format!("Available statuses for {}: {}", id, available.join(", ")),
+                )
+            })?;
+        let body = json!({ "transition": { "id": transition.id } });
+        http::send(self.request("POST", &path), Some(&body))?;
+
+        Ok(())
+    }
+
+    fn assign_issue(&self, id: &str) -> Result<(), DriverError> {
+        let myself = self.myself()?;
+        let body = match (myself.account_id, myself.name) {
+            (Some(account_id), name) if self.cloud || name.is_none() => {
+                json!({ "accountId": account_id })
+            }
+            (_, Some(name)) => json!({ "name": name }),
+            _ => {
+                return Err(Report::new(DriverError::InvalidResponse)
+                    .attach_printable("No account id or name for the authenticated user"))
+            }
+        };
+        let path = format!("/rest/api/2/issue/{}/assignee", id);
+        http::send(self.request("PUT", &path), Some(&body))?;
+
+        Ok(())
+    }
+
+    fn whoami(&self) -> Result<String, DriverError> {
+        let myself = self.myself()?;
+
+        Ok(myself
+            .display_name
+            .or(myself.name)
+            .or(myself.account_id)
+            .unwrap_or_default())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_issue: true,
+            transition: true,
+            assign: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn driver(identifier: &str, jql: &str) -> JiraDriver {
+        JiraDriver::new(
+            "https://jira.example.com",
+            identifier,
+            Some(jql.to_string()),
+            None,
+            Access::Token {
+                token: String::from("token"),
+            },
+        )
+    }
+
+    fn stub_driver(server: &Server, cloud: bool) -> JiraDriver {
+        JiraDriver::new(
+            &server.url(),
+            "ABC",
+            None,
+            Some(cloud),
+            Access::Token {
+                token: String::from("token"),
+            },
+        )
+    }
+
+    fn issues(keys: &[&str]) -> Vec<serde_json::Value> {
+        keys.iter()
+            .map(|key| json!({ "key": key, "fields": { "summary": format!("Title of {}", key) } }))
+            .collect()
+    }
+
+    fn ids(issues: Vec<Issue>) -> Vec<String> {
+        issues.into_iter().map(|issue| issue.id).collect()
+    }
+
+    #[test]
+    fn server_search_pages_with_start_at() {
+        let mut server = Server::new();
+        let first = server
+            .mock("GET", "/rest/api/2/search")
+            .match_header("Authorization", "Bearer token")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(
+                    "jql".into(),
+                    "project = \"ABC\" AND (statusCategory != Done) ORDER BY updated DESC".into(),
+                ),
+                Matcher::UrlEncoded("startAt".into(), "0".into()),
+            ]))
+            .with_body(json!({ "issues": issues(&["ABC-1", "ABC-2"]), "total": 3 }).to_string())
+            .create();
+        let second = server
+            .mock("GET", "/rest/api/2/search")
+            .match_query(Matcher::UrlEncoded("startAt".into(), "2".into()))
+            .with_body(json!({ "issues": issues(&["ABC-3"]), "total": 3 }).to_string())
+            .create();
+
+        let issues = stub_driver(&server, false).get_issues().unwrap();
+
+        first.assert();
+        second.assert();
+        assert_eq!(ids(issues), ["ABC-1", "ABC-2", "ABC-3"]);
+    }
+
+    #[test]
+    fn server_search_stops_on_an_empty_page() {
+        let mut server = Server::new();
+        let page = server
+            .mock("GET", "/rest/api/2/search")
+            .match_query(Matcher::UrlEncoded("startAt".into(), "0".into()))
+            .with_body(json!({ "issues": [], "total": 5 }).to_string())
+            .create();
+
+        let issues = stub_driver(&server, false).get_issues().unwrap();
+
+        page.assert();
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn cloud_search_pages_with_next_page_token() {
+        let mut server = Server::new();
+        let first = server
+            .mock("GET", "/rest/api/2/search/jql")
+            .match_query(Matcher::UrlEncoded("startAt".into(), "0".into()))
+            .with_body(
+                json!({ "issues": issues(&["ABC-1"]), "nextPageToken": "page-2" }).to_string(),
+            )
+            .create();
+        let second = server
+            .mock("GET", "/rest/api/2/search/jql")
+            .match_query(Matcher::UrlEncoded("nextPageToken".into(), "page-2".into()))
+            .with_body(json!({ "issues": issues(&["ABC-2"]) }).to_string())
+            .create();
+
+        let issues = stub_driver(&server, true).get_issues().unwrap();
+
+        first.assert();
+        second.assert();
+        assert_eq!(ids(issues), ["ABC-1", "ABC-2"]);
+    }
+
+    #[test]
+    fn transition_posts_the_transition_to_the_status() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/rest/api/2/issue/ABC-1/transitions")
+            .with_body(
+                json!({ "transitions": [
+                    { "id": "11", "name": "Start", "to": { "name": "In Progress" } },
+                    { "id": "21", "name": "Finish", "to": { "name": "Done" } },
+                ] })
+                .to_string(),
+            )
+            .create();
+        let post = server
+            .mock("POST", "/rest/api/2/issue/ABC-1/transitions")
+            .match_body(Matcher::Json(json!({ "transition": { "id": "11" } })))
+            .create();
+        let driver = stub_driver(&server, false);
+
+        driver.transition_issue("ABC-1", "in progress").unwrap();
+        let error = driver.transition_issue("ABC-1", "Review").unwrap_err();
+
+        post.assert();
+        assert!(matches!(
+            error.current_context(),
+            DriverError::StatusNotFound(status) if status == "Review"
+        ));
+        assert!(format!("{:?}", error).contains("In Progress, Done"));
+    }
+
+    #[test]
+    fn assign_uses_the_account_id_on_cloud() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/rest/api/2/myself")
+            .with_body(json!({ "accountId": "5b10a", "name": "jdoe" }).to_string())
+            .create();
+        let put = server
+            .mock("PUT", "/rest/api/2/issue/ABC-1/assignee")
+            .match_body(Matcher::Json(json!({ "accountId": "5b10a" })))
+            .create();
+
+        stub_driver(&server, true).assign_issue("ABC-1").unwrap();
+
+        put.assert();
+    }
+
+    #[test]
+    fn jql_keeps_order_by_at_the_end() {
+        let jql = driver("ABC", "assignee = currentUser() order by priority").jql();
+
+        assert_eq!(
+            jql,
+            "project = \"ABC\" AND (assignee = currentUser()) order by priority"
+        );
+    }
+
+    #[test]
+    fn jql_splits_order_by_after_non_ascii_text() {
+        let jql = driver("ABC", "summary ~ \"İİİİ\" ORDER BY updated").jql();
+
+        assert_eq!(
+            jql,
+            "project = \"ABC\" AND (summary ~ \"İİİİ\") ORDER BY updated"
+        );
+    }
+
+    #[test]
+    fn jql_ignores_order_by_in_quoted_values() {
+        let jql = driver(
+            "ABC",
+            "summary ~ \"sort order by date\" AND labels = 'order by' ORDER BY rank",
+        )
+        .jql();
+
+        assert_eq!(
+            jql,
+            "project = \"ABC\" AND (summary ~ \"sort order by date\" AND labels = 'order by') ORDER BY rank"
+        );
+        assert_eq!(order_by_index("summary ~ \"a \\\" order by\""), None);
+    }
+
+    #[test]
+    fn jql_is_left_as_is_for_boards() {
+        assert_eq!(driver("42", "ORDER BY rank").jql(), "ORDER BY rank");
+    }
+
+    #[test]
+    fn whoami_prefers_the_display_name() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/rest/api/2/myself")
+            .match_header("Authorization", "Bearer token")
+            .with_body(json!({ "accountId": "5b10", "displayName": "Ada Lovelace" }).to_string())
+            .create();
+
+        assert_eq!(stub_driver(&server, true).whoami().unwrap(), "Ada Lovelace");
+    }
+}
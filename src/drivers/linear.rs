@@ -0,0 +1,456 @@
+// Linear driver using the GraphQL API
+use error_stack::{Report, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::issue::Issue;
+use super::{http, Capabilities, DriverError, IssueDriver};
+use crate::features::global_config::Access;
+
+const PAGE_SIZE: u32 = 50;
+
+const ISSUE_FIELDS: &str = "identifier title description url state { name } assignee { name }
+    creator { name } createdAt updatedAt labels { nodes { name } } priorityLabel";
+
+#[derive(Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct IssuesData {
+    issues: Connection,
+}
+
+#[derive(Deserialize)]
+struct IssueData {
+    issue: LinearIssue,
+}
+
+#[derive(Deserialize)]
+struct StatesData {
+    #[serde(rename = "workflowStates")]
+    workflow_states: Nodes<Identified>,
+}
+
+#[derive(Deserialize)]
+struct ViewerData {
+    viewer: Identified,
+}
+
+#[derive(Deserialize)]
+struct ViewerProfileData {
+    viewer: LinearUser,
+}
+
+#[derive(Deserialize)]
+struct LinearUser {
+    name: String,
+    email: String,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct Identified {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateData {
+    #[serde(rename = "issueUpdate")]
+    issue_update: UpdatePayload,
+}
+
+#[derive(Deserialize)]
+struct UpdatePayload {
+    success: bool,
+}
+
+#[derive(Deserialize)]
+struct Connection {
+    nodes: Vec<LinearIssue>,
+    #[serde(rename = "pageInfo")]
+    page_info: PageInfo,
+}
+
+#[derive(Deserialize)]
+struct PageInfo {
+    #[serde(rename = "hasNextPage")]
+    has_next_page: bool,
+    #[serde(rename = "endCursor")]
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Labels {
+    nodes: Vec<Named>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LinearIssue {
+    // Human identifier, ENG-123
+    identifier: String,
+    title: String,
+    description: Option<String>,
+    url: Option<String>,
+    state: Option<Named>,
+    assignee: Option<Named>,
+    creator: Option<Named>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    labels: Option<Labels>,
+    priority_label: Option<String>,
+}
+
+impl From<LinearIssue> for Issue {
+    fn from(issue: LinearIssue) -> Self {
+        Issue {
+            id: issue.identifier,
+            title: issue.title,
+            description: issue.description,
+            url: issue.url,
+            status: issue.state.map(|state| state.name),
+            assignee: issue.assignee.map(|assignee| assignee.name),
+            reporter: issue.creator.map(|creator| creator.name),
+            created: issue.created_at,
+            updated: issue.updated_at,
+            labels: issue
+                .labels
+                .map(|labels| labels.nodes.into_iter().map(|label| label.name).collect())
+                .unwrap_or_default(),
+            priority: issue.priority_label,
+            issue_type: None,
+        }
+    }
+}
+
+pub struct LinearDriver {
+    // API base URL, https://api.linear.app
+    url: String,
+    team_key: String,
+    token: String,
+}
+
+impl LinearDriver {
+    pub fn new(url: &str, team_key: &str, access: Access) -> Result<LinearDriver, DriverError> {
+        let token = match access {
+            Access::Token { token }
+            | Access::OAuth {
+                access_token: token,
+                ..
+            } => token,
+            _ => {
+                return Err(Report::new(DriverError::UnsupportedAccess)
+                    .attach_printable("Linear driver requires an API key or token access"))
+            }
+        };
+
+        Ok(LinearDriver {
+            url: url.trim_end_matches('/').to_string(),
+            team_key: team_key.to_string(),
+            token,
+        })
+    }
+
+    // Run a GraphQL query, personal API keys are sent as is and OAuth tokens
+    // as Bearer
+    fn query<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T, DriverError> {
+        let authorization = if self.token.starts_with("lin_api_") {
+            self.token.clone()
+        } else {
+            format!("Bearer {}", self.token)
+        };
+        let request =
+            ureq::post(&format!("{}/graphql", self.url)).set("Authorization", &authorization);
+        let body = json!({ "query": query, "variables": variables });
+        let response: GraphqlResponse<T> = http::read_json(http::send(request, Some(&body))?)?;
+
+        match response.data {
+            Some(data) if response.errors.is_empty() => Ok(data),
+            _ => Err(Report::new(DriverError::InvalidResponse).attach_printable(
+                response
+                    .errors
+                    .into_iter()
+                    .map(|error| error.message)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+        }
+    }
+
+    fn update_issue(&self, id: &str, input: Value) -> Result<(), DriverError> {
+        let query = "mutation Update($id: String!, $input: IssueUpdateInput!) {
+            issueUpdate(id: $id, input: $input) { success }
+        }";
+        let data: UpdateData = self.query(query, json!({ "id": id, "input": input }))?;
+
+        if data.issue_update.success {
+            Ok(())
+        } else {
+            Err(Report::new(DriverError::RequestFailed)
+                .attach_printable(format!("Linear did not update {}", id)))
+        }
+    }
+}
+
+impl IssueDriver for LinearDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let query = format!(
+            "query Issues($team: String!, $after: String) {{
+                issues(
+                    first: {PAGE_SIZE},
+                    after: $after,
+                    filter: {{
+                        team: {{ key: {{ eq: $team }} }},
+                        state: {{ type: {{ nin: [\"completed\", \"canceled\"] }} }}
+                    }}
+                ) {{
+                    nodes {{ {ISSUE_FIELDS} }}
+                    pageInfo {{ hasNextPage endCursor }}
+                }}
+            }}"
+        );
+
+        let mut issues = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let data: IssuesData =
+                self.query(&query, json!({ "team": self.team_key, "after": after }))?;
+
+            issues.extend(data.issues.nodes.into_iter().map(Issue::from));
+            match data.issues.page_info.end_cursor {
+                Some(cursor) if data.issues.page_info.has_next_page => after = Some(cursor),
+                _ => break,
+            }
+        }
+
+        Ok(issues)
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        let query = format!("query Issue($id: String!) {{ issue(id: $id) {{ {ISSUE_FIELDS} }} }}");
+        let data: IssueData = self.query(&query, json!({ "id": id }))?;
+
+        Ok(data.issue.into())
+    }
+
+    // The status is the name of a workflow state of the team
+    fn transition_issue(&self, id: &str, status: &str) -> Result<(), DriverError> {
+        let query = "query States($team: String!, $name: String!) {
+            workflowStates(filter: {
+                team: { key: { eq: $team } },
+                name: { eqIgnoreCase: $name }
+            }) { nodes { id } }
+        }";
+        let data: StatesData =
+            self.query(query, json!({ "team": self.team_key, "name": status }))?;
+        let state = data
+            .workflow_states
+            .nodes
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                Report::new(DriverError::StatusNotFound(status.to_string()))
+                    .attach_printable(format!("No such workflow state in team {}", self.team_key))
+            })?;
+
+        self.update_issue(id, json!({ "stateId": state.id }))
+    }
+
+    fn assign_issue(&self, id: &str) -> Result<(), DriverError> {
+        let data: ViewerData = self.query("query { viewer { id } }", json!({}))?;
+
+        self.update_issue(id, json!({ "assigneeId": data.viewer.id }))
+    }
+
+    fn whoami(&self) -> Result<String, DriverError> {
+        let data: ViewerProfileData = self.query("query { viewer { name email } }", json!({}))?;
+
+        Ok(format!("{} <{}>", data.viewer.name, data.viewer.email))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_issue: true,
+            transition: true,
+            assign: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn driver(server: &Server, token: &str) -> LinearDriver {
+        LinearDriver::new(
+            &server.url(),
+            "ENG",
+            Access::Token {
+                token: token.to_string(),
+            },
+        )
+        .unwrap()
+    }
+
+    fn issues_page(ids: &[&str], end_cursor: Option<&str>) -> String {
+        let nodes = ids
+            .iter()
+            .map(|id| json!({ "identifier": id, "title": format!("Title of {}", id) }))
+            .collect::<Vec<_>>();
+
+        json!({ "data": { "issues": {
+            "nodes": nodes,
+            "pageInfo": { "hasNextPage": end_cursor.is_some(), "endCursor": end_cursor },
+        } } })
+        .to_string()
+    }
+
+    #[test]
+    fn issues_follow_the_page_info() {
+        let mut server = Server::new();
+        let first = server
+            .mock("POST", "/graphql")
+            .match_header("Authorization", "lin_api_key")
+            .match_body(Matcher::PartialJson(
+                json!({ "variables": { "team": "ENG", "after": null } }),
+            ))
+            .with_body(issues_page(&["ENG-1", "ENG-2"], Some("cursor-2")))
+            .create();
+        let second = server
+            .mock("POST", "/graphql")
+            .match_body(Matcher::PartialJson(
+                json!({ "variables": { "team": "ENG", "after": "cursor-2" } }),
+            ))
+            .with_body(issues_page(&["ENG-3"], None))
+            .create();
+
+        let issues = driver(&server, "lin_api_key").get_issues().unwrap();
+
+        first.assert();
+        second.assert();
+        let ids = issues.into_iter().map(|issue| issue.id).collect::<Vec<_>>();
+        assert_eq!(ids, ["ENG-1", "ENG-2", "ENG-3"]);
+    }
+
+    #[test]
+    fn oauth_tokens_are_sent_as_bearer() {
+        let mut server = Server::new();
+        let query = server
+            .mock("POST", "/graphql")
+            .match_header("Authorization", "Bearer oauth-token")
+            .with_body(issues_page(&[], None))
+            .create();
+
+        driver(&server, "oauth-token").get_issues().unwrap();
+
+        query.assert();
+    }
+
+    #[test]
+    fn graphql_errors_are_reported() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/graphql")
+            .with_body(
+                json!({ "data": null, "errors": [{ "message": "Entity not found" }] }).to_string(),
+            )
+            .create();
+
+        let error = driver(&server, "lin_api_key")
+            .get_issue("ENG-9")
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            DriverError::InvalidResponse
+        ));
+        assert!(format!("{:?}", error).contains("Entity not found"));
+    }
+
+    #[test]
+    fn transition_updates_the_state_of_the_team() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/graphql")
+            .match_body(Matcher::PartialJson(
+                json!({ "variables": { "team": "ENG", "name": "in progress" } }),
+            ))
+            .with_body(
+                json!({ "data": { "workflowStates": { "nodes": [{ "id": "state-1" }] } } })
+                    .to_string(),
+            )
+            .create();
+        let update = server
+            .mock("POST", "/graphql")
+            .match_body(Matcher::PartialJson(
+                json!({ "variables": { "id": "ENG-1", "input": { "stateId": "state-1" } } }),
+            ))
+            .with_body(json!({ "data": { "issueUpdate": { "success": true } } }).to_string())
+            .create();
+
+        driver(&server, "lin_api_key")
+            .transition_issue("ENG-1", "in progress")
+            .unwrap();
+
+        update.assert();
+    }
+
+    #[test]
+    fn transition_to_an_unknown_state_fails() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/graphql")
+            .with_body(json!({ "data": { "workflowStates": { "nodes": [] } } }).to_string())
+            .create();
+
+        let error = driver(&server, "lin_api_key")
+            .transition_issue("ENG-1", "Review")
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            DriverError::StatusNotFound(status) if status == "Review"
+        ));
+    }
+
+    #[test]
+    fn whoami_is_the_viewer() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/graphql")
+            .match_header("Authorization", "lin_api_key")
+            .match_body(Matcher::PartialJson(
+                json!({ "query": "query { viewer { name email } }" }),
+            ))
+            .with_body(
+                json!({ "data": { "viewer": { "name": "Ada", "email": "ada@example.com" } } })
+                    .to_string(),
+            )
+            .create();
+
+        assert_eq!(
+            driver(&server, "lin_api_key").whoami().unwrap(),
+            "Ada <ada@example.com>"
+        );
+    }
+}
@@ -1,56 +1,35 @@
 // Diver mock for testing
-use error_stack::Result;
-use std::fmt;
-use std::{error::Error, fmt::Display};
+use error_stack::{Report, Result};
 
 use super::issue::Issue;
+use super::{Capabilities, DriverError, IssueDriver};
 
-// Driver Errors
-#[derive(Debug)]
-pub struct MockDriverError(String);
+// Driver that serves a fixed list of issues whatever the board is
+pub struct MockDriver;
 
-impl Display for MockDriverError {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.write_str(&self.0)
+impl IssueDriver for MockDriver {
+    // list of issues from a board.
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        Ok((1..=8)
+            .map(|i| Issue {
+                id: i.to_string(),
+                title: format!("Issue {}", i),
+                ..Default::default()
+            })
+            .collect())
     }
-}
 
-impl Error for MockDriverError {}
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        self.get_issues()?
+            .into_iter()
+            .find(|issue| issue.id == id)
+            .ok_or_else(|| Report::new(DriverError::IssueNotFound(id.to_string())))
+    }
 
-// list of issues from a board.
-pub fn get_issues(_board_id: &str) -> Result<Vec<Issue>, MockDriverError> {
-    Ok(vec![
-        Issue {
-            id: "1".to_string(),
-            title: "Issue 1".to_string(),
-        },
-        Issue {
-            id: "2".to_string(),
-            title: "Issue 2".to_string(),
-        },
-        Issue {
-            id: "3".to_string(),
-            title: "Issue 3".to_string(),
-        },
-        Issue {
-            id: "4".to_string(),
-            title: "Issue 4".to_string(),
-        },
-        Issue {
-            id: "5".to_string(),
-            title: "Issue 5".to_string(),
-        },
-        Issue {
-            id: "6".to_string(),
-            title: "Issue 6".to_string(),
-        },
-        Issue {
-            id: "7".to_string(),
-            title: "Issue 7".to_string(),
-        },
-        Issue {
-            id: "8".to_string(),
-            title: "Issue 8".to_string(),
-        },
-    ])
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_issue: true,
+            ..Default::default()
+        }
+    }
 }
@@ -0,0 +1,624 @@
+// Notion database driver using the public API
+use error_stack::{Report, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::OnceCell;
+
+use super::issue::Issue;
+use super::{http, Capabilities, DriverError, IssueDriver};
+use crate::features::global_config::Access;
+
+const NOTION_VERSION: &str = "2022-06-28";
+const PAGE_SIZE: u32 = 100;
+
+// Database columns mapped onto the issue fields
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Properties {
+    // Title column, "Name" by default
+    #[serde(default = "default_title_property")]
+    pub title: String,
+    // Column used as the issue id (unique id, formula, text or number), the
+    // page id is used when missing
+    #[serde(default)]
+    pub id: Option<String>,
+    // Only list the pages with this status
+    #[serde(default)]
+    pub status: Option<StatusFilter>,
+}
+
+impl Default for Properties {
+    fn default() -> Self {
+        Properties {
+            title: default_title_property(),
+            id: None,
+            status: None,
+        }
+    }
+}
+
+fn default_title_property() -> String {
+    String::from("Name")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatusFilter {
+    pub property: String,
+    pub equals: String,
+    // Type of the column, read from the database schema when missing
+    #[serde(default, rename = "type")]
+    pub kind: Option<StatusKind>,
+}
+
+// Column types that can be filtered on their value name
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum StatusKind {
+    Status,
+    Select,
+}
+
+impl StatusKind {
+    // Name of the type in the Notion API
+    fn api_name(self) -> &'static str {
+        match self {
+            StatusKind::Status => "status",
+            StatusKind::Select => "select",
+        }
+    }
+}
+
+// Integration bot or person owning the token
+#[derive(Deserialize)]
+struct NotionUser {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Database {
+    properties: serde_json::Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct QueryPage {
+    results: Vec<NotionPage>,
+    has_more: bool,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NotionPage {
+    id: String,
+    url: Option<String>,
+    created_time: Option<String>,
+    last_edited_time: Option<String>,
+    properties: serde_json::Map<String, Value>,
+}
+
+// Plain text value of a page property, whatever its type
+fn property_text(property: &Value) -> Option<String> {
+    let kind = property["type"].as_str()?;
+    let value = &property[kind];
+
+    let text = match kind {
+        "title" | "rich_text" => value
+            .as_array()?
+            .iter()
+            .filter_map(|text| text["plain_text"].as_str())
+            .collect::<String>(),
+        "unique_id" => match value["prefix"].as_str() {
+            Some(prefix) => format!("{}-{}", prefix, value["number"]),
+            None => value["number"].to_string(),
+        },
+        "formula" => {
+            let formula_kind = value["type"].as_str()?;
+            match &value[formula_kind] {
+                Value::String(text) => text.clone(),
+                Value::Number(number) => number.to_string(),
+                _ => return None,
+            }
+        }
+        "number" => value.as_number()?.to_string(),
+        "select" | "status" => value["name"].as_str()?.to_string(),
+        _ => return None,
+    };
+
+    Some(text).filter(|text| !text.is_empty())
+}
+
+pub struct NotionDriver {
+    // API base URL, https://api.notion.com
+    url: String,
+    database_id: String,
+    properties: Properties,
+    token: String,
+    // Database schema, read once when a column type is needed
+    database: OnceCell<Database>,
+}
+
+impl NotionDriver {
+    pub fn new(
+        url: &str,
+        database_id: &str,
+        properties: Properties,
+        access: Access,
+    ) -> Result<NotionDriver, DriverError> {
+        let token = match access {
+            Access::Token { token }
+            | Access::OAuth {
+                access_token: token,
+                ..
+            } => token,
+            _ => {
+                return Err(Report::new(DriverError::UnsupportedAccess)
+                    .attach_printable("Notion driver requires an integration token access"))
+            }
+        };
+
+        Ok(NotionDriver {
+            url: url.trim_end_matches('/').to_string(),
+            database_id: database_id.to_string(),
+            properties,
+            token,
+            database: OnceCell::new(),
+        })
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        ureq::request(method, &format!("{}/v1{}", self.url, path))
+            .set("Notion-Version", NOTION_VERSION)
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    fn to_issue(&self, page: NotionPage) -> Issue {
+        let title = page
+            .properties
+            .get(&self.properties.title)
+            .and_then(property_text)
+            .unwrap_or_default();
+        let id = self
+            .properties
+            .id
+            .as_ref()
+            .and_then(|id| page.properties.get(id))
+            .and_then(property_text)
+            .unwrap_or(page.id);
+        let status = self
+            .properties
+            .status
+            .as_ref()
+            .and_then(|status| page.properties.get(&status.property))
+            .and_then(property_text);
+
+        Issue {
+            id,
+            title,
+            url: page.url,
+            status,
+            created: page.created_time,
+            updated: page.last_edited_time,
+            ..Default::default()
+        }
+    }
+
+    // Type of a database column, none when the database does not have it
+    fn column_type(&self, property: &str) -> Result<Option<&str>, DriverError> {
+        if self.database.get().is_none() {
+            let request = self.request("GET", &format!("/databases/{}", self.database_id));
+            let database: Database = http::read_json(http::send(request, None)?)?;
+            self.database.get_or_init(|| database);
+        }
+
+        Ok(self
+            .database
+            .get()
+            .and_then(|database| database.properties.get(property))
+            .and_then(|property| property["type"].as_str()))
+    }
+
+    // Status and select columns need a filter of their own type
+    fn status_kind(&self, status: &StatusFilter) -> Result<StatusKind, DriverError> {
+        if let Some(kind) = status.kind {
+            return Ok(kind);
+        }
+
+        match self.column_type(&status.property)? {
+            Some("status") => Ok(StatusKind::Status),
+            Some("select") => Ok(StatusKind::Select),
+            Some(kind) => Err(
+                Report::new(DriverError::InvalidResponse).attach_printable(format!(
+                    "{} is a {} column, only status and select columns can be filtered",
+                    status.property, kind
+                )),
+            ),
+            None => Err(Report::new(DriverError::InvalidResponse)
+                .attach_printable(format!("No {} column in the database", status.property))),
+        }
+    }
+
+    fn query(&self, filter: Option<Value>) -> Result<Vec<Issue>, DriverError> {
+        let mut issues = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut body = json!({ "page_size": PAGE_SIZE });
+            if let Some(filter) = &filter {
+                body["filter"] = filter.clone();
+            }
+            if let Some(cursor) = &cursor {
+                body["start_cursor"] = json!(cursor);
+            }
+
+            let request = self.request("POST", &format!("/databases/{}/query", self.database_id));
+            let page: QueryPage = http::read_json(http::send(request, Some(&body))?)?;
+
+            issues.extend(page.results.into_iter().map(|page| self.to_issue(page)));
+            match page.next_cursor {
+                Some(next_cursor) if page.has_more => cursor = Some(next_cursor),
+                _ => break,
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+impl IssueDriver for NotionDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        let filter = match &self.properties.status {
+            Some(status) => {
+                let mut filter = json!({ "property": status.property });
+                filter[self.status_kind(status)?.api_name()] = json!({ "equals": status.equals });
+                Some(filter)
+            }
+            None => None,
+        };
+
+        self.query(filter)
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        if self.properties.id.is_none() {
+            let request = self.request("GET", &format!("/pages/{}", id));
+            let page: NotionPage = http::read_json(http::send(request, None)?)?;
+
+            return Ok(self.to_issue(page));
+        }
+
+        let property = self.properties.id.as_deref().unwrap_or_default();
+        if self.column_type(property)? != Some("unique_id") {
+            return Err(
+                Report::new(DriverError::UnsupportedOperation).attach_printable(format!(
+                    "Only unique id columns can be searched, {} is not one",
+                    property
+                )),
+            );
+        }
+
+        // Unique ids are filtered on their number, without the prefix
+        let number = id
+            .rsplit('-')
+            .next()
+            .and_then(|number| number.parse::<u64>().ok())
+            .ok_or_else(|| Report::new(DriverError::IssueNotFound(id.to_string())))?;
+        let filter = json!({ "property": property, "unique_id": { "equals": number } });
+
+        self.query(Some(filter))?
+            .into_iter()
+            .find(|issue| issue.id == id)
+            .ok_or_else(|| Report::new(DriverError::IssueNotFound(id.to_string())))
+    }
+
+    fn whoami(&self) -> Result<String, DriverError> {
+        let request = self.request("GET", "/users/me");
+        let user: NotionUser = http::read_json(http::send(request, None)?)?;
+
+        Ok(format!("{} ({})", user.name.unwrap_or_default(), user.kind))
+    }
+
+    // Pages are fetched by their page id or by a unique id column, the other
+    // id columns can not be filtered on their text
+    fn capabilities(&self) -> Capabilities {
+        let get_issue = match &self.properties.id {
+            Some(property) => matches!(self.column_type(property), Ok(Some("unique_id"))),
+            None => true,
+        };
+
+        Capabilities {
+            get_issue,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn driver(server: &Server, status: Option<StatusFilter>) -> NotionDriver {
+        let properties = Properties {
+            id: Some(String::from("ID")),
+            status,
+            ..Default::default()
+        };
+
+        NotionDriver::new(
+            &server.url(),
+            "db",
+            properties,
+            Access::Token {
+                token: String::from("token"),
+            },
+        )
+        .unwrap()
+    }
+
+    fn page(number: u64) -> Value {
+        json!({
+            "id": format!("page-{}", number),
+            "properties": {
+                "Name": { "type": "title", "title": [{ "plain_text": format!("Task {}", number) }] },
+                "ID": { "type": "unique_id", "unique_id": { "prefix": "TASK", "number": number } },
+            },
+        })
+    }
+
+    fn status_filter(kind: Option<StatusKind>) -> StatusFilter {
+        StatusFilter {
+            property: String::from("Stage"),
+            equals: String::from("Doing"),
+            kind,
+        }
+    }
+
+    #[test]
+    fn property_text_reads_the_property_types() {
+        let texts = [
+            json!({ "type": "title", "title": [{ "plain_text": "Fix " }, { "plain_text": "login" }] }),
+            json!({ "type": "rich_text", "rich_text": [{ "plain_text": "Notes" }] }),
+            json!({ "type": "unique_id", "unique_id": { "prefix": "TASK", "number": 7 } }),
+            json!({ "type": "unique_id", "unique_id": { "prefix": null, "number": 7 } }),
+            json!({ "type": "formula", "formula": { "type": "string", "string": "F-1" } }),
+            json!({ "type": "formula", "formula": { "type": "number", "number": 12 } }),
+            json!({ "type": "number", "number": 3 }),
+            json!({ "type": "select", "select": { "name": "High" } }),
+            json!({ "type": "status", "status": { "name": "Done" } }),
+        ]
+        .iter()
+        .map(property_text)
+        .collect::<Vec<_>>();
+
+        let expected = [
+            "Fix login",
+            "Notes",
+            "TASK-7",
+            "7",
+            "F-1",
+            "12",
+            "3",
+            "High",
+            "Done",
+        ];
+        assert_eq!(texts, expected.map(|text| Some(text.to_string())));
+    }
+
+    #[test]
+    fn property_text_skips_empty_and_unknown_values() {
+        assert_eq!(
+            property_text(&json!({ "type": "title", "title": [] })),
+            None
+        );
+        assert_eq!(
+            property_text(&json!({ "type": "select", "select": null })),
+            None
+        );
+        assert_eq!(
+            property_text(&json!({ "type": "checkbox", "checkbox": true })),
+            None
+        );
+        assert_eq!(
+            property_text(
+                &json!({ "type": "formula", "formula": { "type": "boolean", "boolean": true } })
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn issues_follow_the_cursor() {
+        let mut server = Server::new();
+        let first = server
+            .mock("POST", "/v1/databases/db/query")
+            .match_header("Notion-Version", NOTION_VERSION)
+            .match_body(Matcher::Json(json!({ "page_size": PAGE_SIZE })))
+            .with_body(
+                json!({ "results": [page(1), page(2)], "has_more": true, "next_cursor": "c2" })
+                    .to_string(),
+            )
+            .create();
+        let second = server
+            .mock("POST", "/v1/databases/db/query")
+            .match_body(Matcher::PartialJson(json!({ "start_cursor": "c2" })))
+            .with_body(
+                json!({ "results": [page(3)], "has_more": false, "next_cursor": null }).to_string(),
+            )
+            .create();
+
+        let issues = driver(&server, None).get_issues().unwrap();
+
+        first.assert();
+        second.assert();
+        let issues = issues
+            .into_iter()
+            .map(|issue| (issue.id, issue.title))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            issues,
+            [
+                (String::from("TASK-1"), String::from("Task 1")),
+                (String::from("TASK-2"), String::from("Task 2")),
+                (String::from("TASK-3"), String::from("Task 3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn status_filter_uses_the_configured_type() {
+        let mut server = Server::new();
+        let query = server
+            .mock("POST", "/v1/databases/db/query")
+            .match_body(Matcher::PartialJson(json!({
+                "filter": { "property": "Stage", "select": { "equals": "Doing" } },
+            })))
+            .with_body(json!({ "results": [], "has_more": false }).to_string())
+            .create();
+
+        driver(&server, Some(status_filter(Some(StatusKind::Select))))
+            .get_issues()
+            .unwrap();
+
+        query.assert();
+    }
+
+    #[test]
+    fn status_filter_type_is_read_from_the_database() {
+        let mut server = Server::new();
+        let database = server
+            .mock("GET", "/v1/databases/db")
+            .with_body(
+                json!({ "properties": { "Stage": { "type": "select", "select": {} } } })
+                    .to_string(),
+            )
+            .create();
+        let query = server
+            .mock("POST", "/v1/databases/db/query")
+            .match_body(Matcher::PartialJson(json!({
+                "filter": { "property": "Stage", "select": { "equals": "Doing" } },
+            })))
+            .with_body(json!({ "results": [], "has_more": false }).to_string())
+            .create();
+
+        driver(&server, Some(status_filter(None)))
+            .get_issues()
+            .unwrap();
+
+        database.assert();
+        query.assert();
+    }
+
+    #[test]
+    fn status_filter_rejects_other_column_types() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/v1/databases/db")
+            .with_body(
+                json!({ "properties": { "Stage": { "type": "checkbox", "checkbox": {} } } })
+                    .to_string(),
+            )
+            .create();
+
+        let error = driver(&server, Some(status_filter(None)))
+            .get_issues()
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            DriverError::InvalidResponse
+        ));
+    }
+
+    fn schema(server: &mut Server, id_type: &str) -> mockito::Mock {
+        server
+            .mock("GET", "/v1/databases/db")
+            .with_body(
+                json!({ "properties": {
+                    "ID": { "type": id_type },
+                    "Stage": { "type": "select", "select": {} },
+                } })
+                .to_string(),
+            )
+            .expect(1)
+            .create()
+    }
+
+    #[test]
+    fn unique_id_is_filtered_on_its_number() {
+        let mut server = Server::new();
+        let database = schema(&mut server, "unique_id");
+        let query = server
+            .mock("POST", "/v1/databases/db/query")
+            .match_body(Matcher::PartialJson(json!({
+                "filter": { "property": "ID", "unique_id": { "equals": 2 } },
+            })))
+            .with_body(json!({ "results": [page(2)], "has_more": false }).to_string())
+            .create();
+        // The status filter type comes from the same schema
+        server
+            .mock("POST", "/v1/databases/db/query")
+            .match_body(Matcher::PartialJson(json!({
+                "filter": { "property": "Stage", "select": { "equals": "Doing" } },
+            })))
+            .with_body(json!({ "results": [], "has_more": false }).to_string())
+            .create();
+        let driver = driver(&server, Some(status_filter(None)));
+
+        assert!(driver.capabilities().get_issue);
+        assert_eq!(driver.get_issue("TASK-2").unwrap().title, "Task 2");
+        assert!(matches!(
+            driver.get_issue("TASK-x").unwrap_err().current_context(),
+            DriverError::IssueNotFound(id) if id == "TASK-x"
+        ));
+        assert!(driver.get_issues().unwrap().is_empty());
+
+        database.assert();
+        query.assert();
+    }
+
+    #[test]
+    fn other_id_columns_can_not_be_fetched() {
+        let mut server = Server::new();
+        schema(&mut server, "formula");
+        let driver = driver(&server, None);
+
+        assert!(!driver.capabilities().get_issue);
+        assert!(matches!(
+            driver.get_issue("F-1").unwrap_err().current_context(),
+            DriverError::UnsupportedOperation
+        ));
+    }
+
+    #[test]
+    fn page_id_is_fetched_directly() {
+        let mut server = Server::new();
+        let page = server
+            .mock("GET", "/v1/pages/page-2")
+            .with_body(page(2).to_string())
+            .create();
+        let driver = NotionDriver::new(
+            &server.url(),
+            "db",
+            Properties::default(),
+            Access::Token {
+                token: String::from("token"),
+            },
+        )
+        .unwrap();
+
+        assert!(driver.capabilities().get_issue);
+        assert_eq!(driver.get_issue("page-2").unwrap().id, "page-2");
+        page.assert();
+    }
+
+    #[test]
+    fn whoami_is_the_integration_name() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/v1/users/me")
+            .match_header("Authorization", "Bearer token")
+            .with_body(json!({ "name": "gbc", "type": "bot" }).to_string())
+            .create();
+
+        assert_eq!(driver(&server, None).whoami().unwrap(), "gbc (bot)");
+    }
+}
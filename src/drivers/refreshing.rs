@@ -0,0 +1,211 @@
+// Driver decorator renewing an expired OAuth access token
+use error_stack::Result;
+use std::cell::{Cell, RefCell};
+
+use super::issue::Issue;
+use super::{Capabilities, DriverError, IssueDriver};
+
+// Builds a driver with refreshed credentials
+pub type Refresh = Box<dyn Fn() -> Result<Box<dyn IssueDriver>, DriverError>>;
+
+// Retry once the calls rejected by the issue tracker with a 401, with a driver
+// using a refreshed access token
+pub struct RefreshingDriver {
+    driver: RefCell<Box<dyn IssueDriver>>,
+    refresh: Refresh,
+    refreshed: Cell<bool>,
+}
+
+impl RefreshingDriver {
+    pub fn new(driver: Box<dyn IssueDriver>, refresh: Refresh) -> RefreshingDriver {
+        RefreshingDriver {
+            driver: RefCell::new(driver),
+            refresh,
+            refreshed: Cell::new(false),
+        }
+    }
+
+    fn call<T>(
+        &self,
+        operation: impl Fn(&dyn IssueDriver) -> Result<T, DriverError>,
+    ) -> Result<T, DriverError> {
+        let result = operation(self.driver.borrow().as_ref());
+
+        match result {
+            Err(mut e)
+                if matches!(e.current_context(), DriverError::Unauthorized)
+                    && !self.refreshed.get() =>
+            {
+                self.refreshed.set(true);
+                match (self.refresh)() {
+                    Ok(driver) => {
+                        *self.driver.borrow_mut() = driver;
+                        operation(self.driver.borrow().as_ref())
+                    }
+                    // The answer of the issue tracker is kept, the refresh
+                    // failure only explains why it was not retried
+                    Err(refresh_error) => {
+                        e.extend_one(refresh_error);
+                        Err(e)
+                    }
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+impl IssueDriver for RefreshingDriver {
+    fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+        self.call(|driver| driver.get_issues())
+    }
+
+    fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+        self.call(|driver| driver.get_issue(id))
+    }
+
+    fn transition_issue(&self, id: &str, status: &str) -> Result<(), DriverError> {
+        self.call(|driver| driver.transition_issue(id, status))
+    }
+
+    fn assign_issue(&self, id: &str) -> Result<(), DriverError> {
+        self.call(|driver| driver.assign_issue(id))
+    }
+
+    fn whoami(&self) -> Result<String, DriverError> {
+        self.call(|driver| driver.whoami())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.driver.borrow().capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error_stack::Report;
+    use std::rc::Rc;
+
+    // Driver answering whoami with its name, or rejecting it like the tracker
+    struct StubDriver {
+        name: &'static str,
+        error: Option<fn() -> DriverError>,
+        calls: Rc<Cell<u32>>,
+    }
+
+    impl IssueDriver for StubDriver {
+        fn get_issues(&self) -> Result<Vec<Issue>, DriverError> {
+            Ok(Vec::new())
+        }
+
+        fn get_issue(&self, id: &str) -> Result<Issue, DriverError> {
+            Err(Report::new(DriverError::IssueNotFound(id.to_string())))
+        }
+
+        fn whoami(&self) -> Result<String, DriverError> {
+            self.calls.set(self.calls.get() + 1);
+            match self.error {
+                Some(error) => {
+                    Err(Report::new(error())
+                        .attach_printable(format!("{} was rejected", self.name)))
+                }
+                None => Ok(self.name.to_string()),
+            }
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+    }
+
+    struct Calls {
+        tracker: Rc<Cell<u32>>,
+        refresh: Rc<Cell<u32>>,
+    }
+
+    fn driver(
+        error: Option<fn() -> DriverError>,
+        refreshed_error: Option<fn() -> DriverError>,
+        refresh_fails: bool,
+    ) -> (RefreshingDriver, Calls) {
+        let calls = Calls {
+            tracker: Rc::new(Cell::new(0)),
+            refresh: Rc::new(Cell::new(0)),
+        };
+        let stub = StubDriver {
+            name: "expired",
+            error,
+            calls: calls.tracker.clone(),
+        };
+        let (tracker, refresh) = (calls.tracker.clone(), calls.refresh.clone());
+        let driver = RefreshingDriver::new(
+            Box::new(stub),
+            Box::new(move || {
+                refresh.set(refresh.get() + 1);
+                if refresh_fails {
+                    return Err(Report::new(DriverError::Unauthorized)
+                        .attach_printable("The refresh token was revoked"));
+                }
+                Ok(Box::new(StubDriver {
+                    name: "refreshed",
+                    error: refreshed_error,
+                    calls: tracker.clone(),
+                }) as Box<dyn IssueDriver>)
+            }),
+        );
+        (driver, calls)
+    }
+
+    #[test]
+    fn retries_with_refreshed_credentials_on_unauthorized() {
+        let (driver, calls) = driver(Some(|| DriverError::Unauthorized), None, false);
+
+        assert_eq!(driver.whoami().unwrap(), "refreshed");
+        assert_eq!(driver.whoami().unwrap(), "refreshed");
+        assert_eq!(calls.refresh.get(), 1);
+        assert_eq!(calls.tracker.get(), 3);
+    }
+
+    #[test]
+    fn retries_only_once() {
+        let (driver, calls) = driver(
+            Some(|| DriverError::Unauthorized),
+            Some(|| DriverError::Unauthorized),
+            false,
+        );
+
+        let error = driver.whoami().unwrap_err();
+        assert!(matches!(error.current_context(), DriverError::Unauthorized));
+        assert!(format!("{:?}", error).contains("refreshed was rejected"));
+        assert!(driver.whoami().is_err());
+
+        assert_eq!(calls.refresh.get(), 1);
+        assert_eq!(calls.tracker.get(), 3);
+    }
+
+    #[test]
+    fn does_not_retry_on_forbidden() {
+        let (driver, calls) = driver(Some(|| DriverError::Forbidden), None, false);
+
+        let error = driver.whoami().unwrap_err();
+
+        assert!(matches!(error.current_context(), DriverError::Forbidden));
+        assert_eq!(calls.refresh.get(), 0);
+        assert_eq!(calls.tracker.get(), 1);
+    }
+
+    #[test]
+    fn keeps_the_tracker_error_when_the_refresh_fails() {
+        let (driver, calls) = driver(Some(|| DriverError::Unauthorized), None, true);
+
+        let error = driver.whoami().unwrap_err();
+
+        assert!(matches!(error.current_context(), DriverError::Unauthorized));
+        let message = format!("{:?}", error);
+        assert!(message.contains("expired was rejected"));
+        assert!(message.contains("The refresh token was revoked"));
+        assert_eq!(calls.refresh.get(), 1);
+        assert_eq!(calls.tracker.get(), 1);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod global_config;
 pub mod init;
+pub mod oauth;
+pub mod pull_request;
@@ -1,30 +1,370 @@
 // Generate a global config file from the command line propt and save
 // it to the user's configuration directory
 
-use error_stack::{Report, Result};
+use error_stack::{Report, Result, ResultExt};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::path::PathBuf;
 use std::{error::Error, fmt::Display};
 
-// Access to the different APIs Bearer or Basic Auth
-#[derive(Serialize, Deserialize, Debug)]
+use crate::secrets::{self, SecretStore};
+
+// Replaces the secrets in the displayed configuration
+const SECRET_MASK: &str = "********";
+
+// Access to the different APIs Bearer or Basic Auth, the password and the
+// token are empty when they are kept in a secret store. The secret can also be
+// read from an external source each time it is used, giving a Basic Auth with
+// the username or a token without.
+#[derive(Serialize, Deserialize, Clone)]
 pub enum Access {
-    Basic { username: String, password: String },
-    Token { token: String },
+    Basic {
+        username: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        password: String,
+    },
+    Token {
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        token: String,
+    },
+    // Environment variable holding the secret, for the CI and the containers
+    Env {
+        variable: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    // Command printing the secret on its first line, `pass show gbc/jira`
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    // File holding the secret, a mounted container secret
+    File {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    // Tokens of `gbc login`, refreshed from the token endpoint when rejected.
+    // The client secret is only needed by the confidential clients (Jira).
+    OAuth {
+        client_id: String,
+        token_url: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        access_token: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        refresh_token: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_secret: Option<String>,
+    },
+}
+
+// The secrets are masked in the error reports as well
+impl fmt::Debug for Access {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Basic { username, password } => fmt
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &mask(password))
+                .finish(),
+            Access::Token { token } => fmt
+                .debug_struct("Token")
+                .field("token", &mask(token))
+                .finish(),
+            Access::Env { variable, username } => fmt
+                .debug_struct("Env")
+                .field("variable", variable)
+                .field("username", username)
+                .finish(),
+            Access::Command {
+                command,
+                args,
+                username,
+            } => fmt
+                .debug_struct("Command")
+                .field("command", command)
+                .field("args", args)
+                .field("username", username)
+                .finish(),
+            Access::File { path, username } => fmt
+                .debug_struct("File")
+                .field("path", path)
+                .field("username", username)
+                .finish(),
+            Access::OAuth {
+                client_id,
+                token_url,
+                access_token,
+                refresh_token,
+                client_secret,
+            } => fmt
+                .debug_struct("OAuth")
+                .field("client_id", client_id)
+                .field("token_url", token_url)
+                .field("access_token", &mask(access_token))
+                .field("refresh_token", &refresh_token.as_deref().map(mask))
+                .field("client_secret", &client_secret.as_deref().map(mask))
+                .finish(),
+        }
+    }
+}
+
+// Mask of a secret, empty when the secret is in a store
+fn mask(secret: &str) -> String {
+    if secret.is_empty() {
+        String::new()
+    } else {
+        String::from(SECRET_MASK)
+    }
+}
+
+// OAuth tokens, and client secret, kept together as a single secret in the
+// secret stores
+#[derive(Serialize, Deserialize, Default)]
+struct OAuthTokens {
+    access_token: String,
+    refresh_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+}
+
+impl Access {
+    // Lowercase name of the access type, the one asked by the prompt
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Access::Basic { .. } => "basic",
+            Access::Token { .. } => "token",
+            Access::Env { .. } => "env",
+            Access::Command { .. } => "command",
+            Access::File { .. } => "file",
+            Access::OAuth { .. } => "oauth",
+        }
+    }
+
+    // Secret saved with the access, none for the external sources
+    fn secret(&self) -> Option<String> {
+        match self {
+            Access::Basic { password, .. } => Some(password.clone()),
+            Access::Token { token } => Some(token.clone()),
+            Access::OAuth {
+                access_token,
+                refresh_token,
+                client_secret,
+                ..
+            } => serde_json::to_string(&OAuthTokens {
+                access_token: access_token.clone(),
+                refresh_token: refresh_token.clone(),
+                client_secret: client_secret.clone(),
+            })
+            .ok(),
+            _ => None,
+        }
+    }
+
+    fn with_secret(&self, secret: String) -> Access {
+        match self {
+            Access::Basic { username, .. } => Access::Basic {
+                username: username.clone(),
+                password: secret,
+            },
+            Access::OAuth {
+                client_id,
+                token_url,
+                ..
+            } => {
+                let tokens: OAuthTokens = serde_json::from_str(&secret).unwrap_or_default();
+                Access::OAuth {
+                    client_id: client_id.clone(),
+                    token_url: token_url.clone(),
+                    access_token: tokens.access_token,
+                    refresh_token: tokens.refresh_token,
+                    client_secret: tokens.client_secret,
+                }
+            }
+            _ => Access::Token { token: secret },
+        }
+    }
+
+    // Copy of the access with its secrets masked, to be displayed
+    fn redacted(&self) -> Access {
+        match self {
+            Access::Basic { username, password } => Access::Basic {
+                username: username.clone(),
+                password: mask(password),
+            },
+            Access::Token { token } => Access::Token { token: mask(token) },
+            Access::OAuth {
+                client_id,
+                token_url,
+                access_token,
+                refresh_token,
+                client_secret,
+            } => Access::OAuth {
+                client_id: client_id.clone(),
+                token_url: token_url.clone(),
+                access_token: mask(access_token),
+                refresh_token: refresh_token.as_deref().map(mask),
+                client_secret: client_secret.as_deref().map(mask),
+            },
+            access => access.clone(),
+        }
+    }
+
+    // Read the secret of the external sources, the other accesses are already
+    // complete
+    fn resolve(&self) -> Result<Access, ConfigError> {
+        let (secret, username) = match self {
+            Access::Env { variable, username } => {
+                let secret = std::env::var(variable).map_err(|_| {
+                    Report::new(ConfigError::SecretSourceError)
+                        .attach_printable(format!("Environment variable {} is not set", variable))
+                })?;
+                (secret, username)
+            }
+            Access::Command {
+                command,
+                args,
+                username,
+            } => {
+                let output = std::process::Command::new(command)
+                    .args(args)
+                    .stderr(std::process::Stdio::inherit())
+                    .output()
+                    .map_err(|e| {
+                        Report::new(ConfigError::SecretSourceError)
+                            .attach_printable(format!("Could not run {}: {}", command, e))
+                    })?;
+                if !output.status.success() {
+                    return Err(Report::new(ConfigError::SecretSourceError)
+                        .attach_printable(format!("{} failed with {}", command, output.status)));
+                }
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let secret = stdout.lines().next().unwrap_or_default().to_string();
+                (secret, username)
+            }
+            Access::File { path, username } => {
+                let secret = std::fs::read_to_string(path).map_err(|e| {
+                    Report::new(ConfigError::SecretSourceError).attach_printable(format!(
+                        "Could not read {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                (secret, username)
+            }
+            _ => return Ok(self.clone()),
+        };
+
+        let secret = secret.trim().to_string();
+        if secret.is_empty() {
+            return Err(Report::new(ConfigError::SecretSourceError)
+                .attach_printable(format!("Empty secret from {:?}", self)));
+        }
+
+        Ok(match username {
+            Some(username) => Access::Basic {
+                username: username.clone(),
+                password: secret,
+            },
+            None => Access::Token { token: secret },
+        })
+    }
 }
 
 // The different APIs that gbc can interact with
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Credentials {
     pub url: String,
     pub access: Access,
+    // Where the secret of the access is kept
+    #[serde(default, skip_serializing_if = "SecretStore::is_plaintext")]
+    pub store: SecretStore,
+}
+
+impl Credentials {
+    // Credentials with their secret read from its store
+    fn resolve(&self) -> Result<Credentials, ConfigError> {
+        let access = match secrets::backend(self.store) {
+            Some(backend) => backend
+                .get(&self.url)
+                .change_context(ConfigError::SecretNotAvailable)
+                .map(|secret| self.access.with_secret(secret))?,
+            None => self
+                .access
+                .resolve()
+                .attach_printable_lazy(|| format!("Credentials of {}", self.url))?,
+        };
+
+        Ok(Credentials {
+            access,
+            ..self.clone()
+        })
+    }
+
+    // Credentials with their secret read from its store, unlike resolve the
+    // external sources are kept as configured
+    fn load_secret(&self) -> Result<Credentials, ConfigError> {
+        match secrets::backend(self.store) {
+            Some(_) => self.resolve(),
+            None => Ok(self.clone()),
+        }
+    }
+
+    // Credentials to display, with their secret masked or, to reveal it, read
+    // from its store
+    fn displayed(&self, show_secrets: bool) -> Result<Credentials, ConfigError> {
+        if show_secrets {
+            return self.load_secret();
+        }
+
+        Ok(Credentials {
+            access: self.access.redacted(),
+            ..self.clone()
+        })
+    }
+
+    // Move the secret of resolved credentials to a store, the returned
+    // credentials only keep a reference to it
+    fn store_secret(self, store: SecretStore) -> Result<Credentials, ConfigError> {
+        // The external sources are read when used, there is nothing to store
+        let Some(secret) = self.access.secret() else {
+            return Ok(self);
+        };
+        let Some(backend) = secrets::backend(store) else {
+            return Ok(Credentials { store, ..self });
+        };
+        backend
+            .set(&self.url, &secret)
+            .change_context(ConfigError::SecretNotAvailable)?;
+
+        Ok(Credentials {
+            access: self.access.with_secret(String::new()),
+            store,
+            ..self
+        })
+    }
+
+    // Remove the secret from its store, when it is not in the config file
+    fn delete_secret(&self) -> Result<(), ConfigError> {
+        match secrets::backend(self.store) {
+            Some(backend) => backend
+                .delete(&self.url)
+                .change_context(ConfigError::SecretNotAvailable),
+            None => Ok(()),
+        }
+    }
 }
 
 // Config file structure
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub version: String,
+    // Store of the secrets of the new credentials
+    #[serde(default)]
+    pub secret_store: SecretStore,
     pub credentials: Vec<Credentials>,
 }
 
@@ -41,9 +381,12 @@ pub enum ConfigError {
     FileNotFound,
     FileNotCreated,
     CredentialsNotFound,
+    CredentialsAlreadyExist,
     ParseError,
     SerializationError,
     NoConfigDirectoryAvailable,
+    SecretNotAvailable,
+    SecretSourceError,
 }
 
 impl Display for ConfigError {
@@ -52,11 +395,14 @@ impl Display for ConfigError {
             ConfigError::FileNotFound => fmt.write_str("Configuration file not found"),
             ConfigError::FileNotCreated => fmt.write_str("Configuration file not created"),
             ConfigError::CredentialsNotFound => fmt.write_str("Credentials not found"),
+            ConfigError::CredentialsAlreadyExist => fmt.write_str("Credentials already exist"),
             ConfigError::ParseError => fmt.write_str("Error parsing configuration file"),
             ConfigError::SerializationError => fmt.write_str("Error serializing configuration"),
             ConfigError::NoConfigDirectoryAvailable => {
                 fmt.write_str("Error we did not find a configuration directory")
             }
+            ConfigError::SecretNotAvailable => fmt.write_str("Could not access the secret store"),
+            ConfigError::SecretSourceError => fmt.write_str("Could not read the secret"),
         }
     }
 }
@@ -67,6 +413,7 @@ impl Config {
     pub fn new() -> Config {
         Config {
             version: env!("CARGO_PKG_VERSION").to_string(),
+            secret_store: SecretStore::preferred(),
             credentials: Vec::new(),
         }
     }
@@ -97,32 +444,129 @@ impl Config {
         if !get_config_path()?.exists() {
             return Config::create();
         }
-        Config::read()
+        let config = Config::read()?;
+
+        let plaintext = config.plaintext_secrets();
+        if !plaintext.is_empty() {
+            eprintln!(
+                "Warning: the secrets of {} are in plaintext in {}, move them with 'gbc global-config migrate-secrets keyring' or 'encrypted-file'",
+                plaintext.join(", "),
+                get_config_path()?.display()
+            );
+        }
+
+        Ok(config)
+    }
+
+    // URLs of the credentials keeping their secret in the configuration file,
+    // the configurations predating the secret stores keep them there
+    fn plaintext_secrets(&self) -> Vec<&str> {
+        self.credentials
+            .iter()
+            .filter(|credentials| {
+                credentials.store.is_plaintext() && credentials.access.secret().is_some()
+            })
+            .map(|credentials| credentials.url.as_str())
+            .collect()
     }
 
-    pub fn get_credentials(&self, url: String) -> Result<&Credentials, ConfigError> {
+    fn position(&self, url: &str) -> Result<usize, ConfigError> {
         self.credentials
             .iter()
-            .find(|c| c.url == url)
+            .position(|c| c.url == url)
             .ok_or_else(|| {
                 Report::new(ConfigError::CredentialsNotFound)
                     .attach_printable(format!("Credentials for {} not found", url))
             })
     }
 
+    // Credentials of the URL with their secret read from its store
+    pub fn get_credentials(&self, url: String) -> Result<Credentials, ConfigError> {
+        self.credentials[self.position(&url)?].resolve()
+    }
+
+    // Credentials of the URL to edit, the external sources are not resolved
+    pub fn get_stored_credentials(&self, url: &str) -> Result<Credentials, ConfigError> {
+        self.credentials[self.position(url)?].load_secret()
+    }
+
+    // Save credentials with their secret in the configured secret store
     pub fn create_or_update_credentials(
         &mut self,
         credentials: Credentials,
     ) -> Result<(), ConfigError> {
+        let credentials = credentials.store_secret(self.secret_store)?;
         let index = self
             .credentials
             .iter()
             .position(|c| c.url == credentials.url);
         match index {
-            Some(i) => self.credentials[i] = credentials,
+            Some(i) => {
+                // The previous secret would be left behind in another store
+                if self.credentials[i].store != credentials.store {
+                    self.credentials[i].delete_secret()?;
+                }
+                self.credentials[i] = credentials
+            }
             None => self.credentials.push(credentials),
         }
 
+        self.save()
+    }
+
+    // Replace the credentials of a URL with edited ones, possibly for another
+    // URL. The secret of the renamed credentials moves to the new URL.
+    pub fn replace_credentials(
+        &mut self,
+        url: &str,
+        credentials: Credentials,
+    ) -> Result<(), ConfigError> {
+        let index = self.position(url)?;
+        if credentials.url == url {
+            return self.create_or_update_credentials(credentials);
+        }
+        if self.position(&credentials.url).is_ok() {
+            return Err(Report::new(ConfigError::CredentialsAlreadyExist)
+                .attach_printable(format!("Credentials for {} already exist", credentials.url)));
+        }
+
+        let renamed = credentials.store_secret(self.secret_store)?;
+        // Saved before the old secret is deleted, a failure does not lose it
+        let previous = std::mem::replace(&mut self.credentials[index], renamed);
+        self.save()?;
+        previous.delete_secret()
+    }
+
+    // Remove the credentials of a URL and their secret from its store
+    pub fn remove_credentials(&mut self, url: &str) -> Result<(), ConfigError> {
+        let index = self.position(url)?;
+        self.credentials[index].delete_secret()?;
+        self.credentials.remove(index);
+
+        self.save()
+    }
+
+    // Move every secret to the store, and use it for the new credentials
+    pub fn migrate_secrets(&mut self, store: SecretStore) -> Result<(), ConfigError> {
+        for i in 0..self.credentials.len() {
+            let current = &self.credentials[i];
+            if current.store == store || current.access.secret().is_none() {
+                continue;
+            }
+
+            let migrated = current.resolve()?.store_secret(store)?;
+            // Saved after each move, a failure does not lose any secret
+            let previous = std::mem::replace(&mut self.credentials[i], migrated);
+            self.save()?;
+            previous.delete_secret()?;
+            println!("Moved the secret of {} to {:?}", previous.url, store);
+        }
+
+        self.secret_store = store;
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), ConfigError> {
         serde_json::to_string_pretty(self)
             .map_err(|_| {
                 Report::new(ConfigError::ParseError)
@@ -136,18 +580,257 @@ impl Config {
                     ))
                 })
             })
-            .map(|_| ())
     }
 
-    pub fn print(&self) -> Result<(), ConfigError> {
-        // TODO: hide the passwords in the output
-        serde_json::to_string_pretty(&self)
+    // Print the URL of every credentials with their access type and store
+    pub fn list(&self) {
+        for credentials in &self.credentials {
+            match credentials.access.secret() {
+                Some(_) => println!(
+                    "{} ({}, {:?})",
+                    credentials.url,
+                    credentials.access.kind(),
+                    credentials.store
+                ),
+                None => println!("{} ({})", credentials.url, credentials.access.kind()),
+            }
+        }
+    }
+
+    // Print the configuration, the secrets are masked unless show_secrets
+    pub fn print(&self, show_secrets: bool) -> Result<(), ConfigError> {
+        let config = Config {
+            version: self.version.clone(),
+            secret_store: self.secret_store,
+            credentials: self
+                .credentials
+                .iter()
+                .map(|credentials| credentials.displayed(show_secrets))
+                .collect::<Result<_, _>>()?,
+        };
+
+        serde_json::to_string_pretty(&config)
             .map(|s| {
                 println!("{}", s);
             })
             .map_err(|_| {
                 Report::new(ConfigError::ParseError)
-                    .attach_printable(format!("Could not serialize configuration {:?}", self))
+                    .attach_printable(format!("Could not serialize configuration {:?}", config))
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_masks_the_secrets() {
+        let accesses = [
+            Access::Basic {
+                username: String::from("jdoe"),
+                password: String::from("hunter2"),
+            },
+            Access::Token {
+                token: String::from("hunter2"),
+            },
+            Access::OAuth {
+                client_id: String::from("client"),
+                token_url: String::from("https://example.com/token"),
+                access_token: String::from("hunter2"),
+                refresh_token: Some(String::from("hunter2")),
+                client_secret: Some(String::from("hunter2")),
+            },
+        ];
+
+        for access in accesses {
+            let debug = format!("{:?}", access);
+            assert!(!debug.contains("hunter2"), "{}", debug);
+            assert!(debug.contains(SECRET_MASK), "{}", debug);
+        }
+    }
+
+    #[test]
+    fn debug_keeps_the_empty_secrets_of_the_stores() {
+        let access = Access::Token {
+            token: String::new(),
+        };
+
+        assert_eq!(format!("{:?}", access), "Token { token: \"\" }");
+    }
+
+    #[test]
+    fn config_errors_do_not_leak_the_secrets() {
+        let config = Config {
+            version: String::from("0.1.0"),
+            secret_store: SecretStore::Plaintext,
+            credentials: vec![Credentials {
+                url: String::from("https://api.github.com"),
+                access: Access::Token {
+                    token: String::from("hunter2"),
+                },
+                store: SecretStore::Plaintext,
+            }],
+        };
+
+        assert!(!format!("{:?}", config).contains("hunter2"));
+    }
+
+    fn github_token(token: &str) -> Credentials {
+        Credentials {
+            url: String::from("https://api.github.com"),
+            access: Access::Token {
+                token: token.to_string(),
+            },
+            store: SecretStore::Plaintext,
+        }
+    }
+
+    #[test]
+    fn migrates_secrets_to_the_encrypted_file_and_back() {
+        let home = crate::secrets::tests::ConfigHome::new();
+        let read = |file: &str| std::fs::read_to_string(home.path(file)).unwrap_or_default();
+        let mut config = Config {
+            version: String::from("0.1.0"),
+            secret_store: SecretStore::Plaintext,
+            credentials: vec![github_token("hunter2")],
+        };
+        config.save().unwrap();
+        assert!(read("config.json").contains("hunter2"));
+
+        config.migrate_secrets(SecretStore::EncryptedFile).unwrap();
+
+        let saved = Config::load().unwrap();
+        assert!(!read("config.json").contains("hunter2"));
+        assert!(home.path("secrets.age").exists());
+        assert_eq!(saved.secret_store, SecretStore::EncryptedFile);
+        assert_eq!(saved.credentials[0].store, SecretStore::EncryptedFile);
+        assert!(matches!(
+            saved.get_credentials(String::from("https://api.github.com")).unwrap().access,
+            Access::Token { token } if token == "hunter2"
+        ));
+
+        config.migrate_secrets(SecretStore::Plaintext).unwrap();
+
+        assert!(read("config.json").contains("hunter2"));
+        assert!(matches!(
+            secrets::backend(SecretStore::EncryptedFile)
+                .unwrap()
+                .get("https://api.github.com")
+                .unwrap_err()
+                .current_context(),
+            secrets::SecretError::SecretNotFound(_)
+        ));
+    }
+
+    #[test]
+    fn finds_the_plaintext_secrets() {
+        let config = Config {
+            version: String::from("0.1.0"),
+            secret_store: SecretStore::Plaintext,
+            credentials: vec![
+                github_token("hunter2"),
+                Credentials {
+                    url: String::from("https://gitlab.com"),
+                    access: Access::Env {
+                        variable: String::from("GITLAB_TOKEN"),
+                        username: None,
+                    },
+                    store: SecretStore::Plaintext,
+                },
+                Credentials {
+                    url: String::from("https://linear.app"),
+                    store: SecretStore::Keyring,
+                    ..github_token("")
+                },
+            ],
+        };
+
+        assert_eq!(config.plaintext_secrets(), vec!["https://api.github.com"]);
+    }
+
+    fn command(script: &str, username: Option<&str>) -> Access {
+        Access::Command {
+            command: String::from("sh"),
+            args: vec![String::from("-c"), script.to_string()],
+            username: username.map(String::from),
+        }
+    }
+
+    fn source_error(access: Access) -> String {
+        let error = access.resolve().unwrap_err();
+        assert!(matches!(
+            error.current_context(),
+            ConfigError::SecretSourceError
+        ));
+        format!("{:?}", error)
+    }
+
+    #[test]
+    fn resolves_an_environment_variable() {
+        std::env::set_var("GBC_TEST_RESOLVE_TOKEN", "  hunter2\n");
+
+        let access = Access::Env {
+            variable: String::from("GBC_TEST_RESOLVE_TOKEN"),
+            username: None,
+        };
+
+        assert!(matches!(
+            access.resolve().unwrap(),
+            Access::Token { token } if token == "hunter2"
+        ));
+    }
+
+    #[test]
+    fn reports_an_unset_environment_variable() {
+        let access = Access::Env {
+            variable: String::from("GBC_TEST_RESOLVE_UNSET"),
+            username: None,
+        };
+
+        assert!(source_error(access).contains("GBC_TEST_RESOLVE_UNSET"));
+    }
+
+    #[test]
+    fn resolves_the_first_line_of_a_command_with_its_username() {
+        let access = command("printf 'hunter2 \\nsecond line\\n'", Some("jdoe"));
+
+        assert!(matches!(
+            access.resolve().unwrap(),
+            Access::Basic { username, password } if username == "jdoe" && password == "hunter2"
+        ));
+    }
+
+    #[test]
+    fn reports_a_failing_or_silent_command() {
+        assert!(source_error(command("exit 3", None)).contains("failed"));
+        assert!(source_error(command("true", None)).contains("Empty secret"));
+    }
+
+    #[test]
+    fn resolves_a_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "hunter2\n").unwrap();
+
+        let access = Access::File {
+            path,
+            username: None,
+        };
+
+        assert!(matches!(
+            access.resolve().unwrap(),
+            Access::Token { token } if token == "hunter2"
+        ));
+    }
+
+    #[test]
+    fn reports_a_missing_file() {
+        let access = Access::File {
+            path: PathBuf::from("/nonexistent/gbc/token"),
+            username: None,
+        };
+
+        assert!(source_error(access).contains("/nonexistent/gbc/token"));
+    }
+}
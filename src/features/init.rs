@@ -5,11 +5,17 @@ use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::{error::Error, fmt::Display};
 
+use crate::drivers::Driver;
+use crate::features::pull_request::PullRequestConfig;
+use crate::git::DirtyPolicy;
+use crate::template::Template;
+
 // Error ProjectConfig
 #[derive(Debug)]
 pub enum ProjectConfigError {
     InvalidPath,
     InvalidConfig,
+    MalformedTemplate,
 }
 
 impl Display for ProjectConfigError {
@@ -17,6 +23,7 @@ impl Display for ProjectConfigError {
         match self {
             ProjectConfigError::InvalidPath => write!(f, "Invalid path"),
             ProjectConfigError::InvalidConfig => write!(f, "Invalid config"),
+            ProjectConfigError::MalformedTemplate => write!(f, "Invalid template"),
         }
     }
 }
@@ -28,7 +35,49 @@ impl Error for ProjectConfigError {}
 pub struct ProjectConfig {
     pub identifier: String,
     pub url: String,
+    // Issue tracker driver, the mock driver is used when missing
+    #[serde(default)]
+    pub driver: Driver,
     pub branch_kinds: Vec<BranchKind>,
+    // Max length of the slugs made out of the issue titles
+    #[serde(default = "default_slug_max_length")]
+    pub slug_max_length: Option<usize>,
+    // Git remote used to sync the base branch
+    #[serde(default = "default_remote")]
+    pub remote: String,
+    // Fetch and fast-forward the base branch before creating a new branch
+    #[serde(default)]
+    pub sync: bool,
+    // What to do with the uncommitted changes when switching branches
+    #[serde(default)]
+    pub dirty_policy: DirtyPolicy,
+    // Push the new branches to the remote and track them
+    #[serde(default)]
+    pub push: bool,
+    // Create the branches in a new linked worktree instead of switching HEAD
+    #[serde(default)]
+    pub worktree: bool,
+    // Template of the worktree directory, relative to the repository
+    #[serde(default = "default_worktree_path")]
+    pub worktree_path: String,
+    // Changes made to the issue once its branch is created
+    #[serde(default)]
+    pub on_start: OnStart,
+    // Draft pull requests opened for the new branches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<PullRequestConfig>,
+}
+
+fn default_worktree_path() -> String {
+    String::from("../{repo}-{id}")
+}
+
+fn default_remote() -> String {
+    String::from("origin")
+}
+
+fn default_slug_max_length() -> Option<usize> {
+    Some(50)
 }
 
 // Struct to handle branch kinds
@@ -43,6 +92,20 @@ pub struct ProjectConfig {
 pub struct BranchKind {
     pub kind: String,
     pub formatter: String,
+    // Branch or ref the new branches are cut from, HEAD when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+}
+
+// Issue changes made by `gbc new`, when the driver supports them
+#[derive(Serialize, Deserialize, Default)]
+pub struct OnStart {
+    // Status the issue is moved to, "In Progress"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    // Assign the issue to the authenticated user
+    #[serde(default)]
+    pub assign: bool,
 }
 
 // Function with the configuration file path
@@ -65,32 +128,48 @@ impl Default for ProjectConfig {
         ProjectConfig {
             identifier: String::from(""),
             url: String::from(""),
+            driver: Driver::default(),
             branch_kinds: vec![
                 BranchKind {
                     kind: String::from("feature"),
                     formatter: String::from("feature/{id}-{name}"),
+                    base: None,
                 },
                 BranchKind {
                     kind: String::from("bugfix"),
                     formatter: String::from("bugfix/{id}-{name}"),
+                    base: None,
                 },
                 BranchKind {
                     kind: String::from("release"),
                     formatter: String::from("release/{id}-{name}"),
+                    base: None,
                 },
                 BranchKind {
                     kind: String::from("hotfix"),
                     formatter: String::from("hotfix/{id}-{name}"),
+                    base: None,
                 },
                 BranchKind {
                     kind: String::from("support"),
                     formatter: String::from("support/{id}-{name}"),
+                    base: None,
                 },
                 BranchKind {
                     kind: String::from("other"),
                     formatter: String::from("other/{id}-{name}"),
+                    base: None,
                 },
             ],
+            slug_max_length: default_slug_max_length(),
+            remote: default_remote(),
+            sync: false,
+            dirty_policy: DirtyPolicy::default(),
+            push: false,
+            worktree: false,
+            worktree_path: default_worktree_path(),
+            on_start: OnStart::default(),
+            pull_request: None,
         }
     }
 }
@@ -100,12 +179,23 @@ impl ProjectConfig {
     pub fn new(
         identifier: String,
         url: String,
+        driver: Driver,
         branch_kinds: Option<Vec<BranchKind>>,
     ) -> ProjectConfig {
         ProjectConfig {
             identifier,
             url,
+            driver,
             branch_kinds: branch_kinds.unwrap_or(ProjectConfig::default().branch_kinds),
+            slug_max_length: default_slug_max_length(),
+            remote: default_remote(),
+            sync: false,
+            dirty_policy: DirtyPolicy::default(),
+            push: false,
+            worktree: false,
+            worktree_path: default_worktree_path(),
+            on_start: OnStart::default(),
+            pull_request: None,
         }
     }
 
@@ -113,10 +203,61 @@ impl ProjectConfig {
     pub fn load() -> Result<ProjectConfig, ProjectConfigError> {
         let file = get_local_config_file()?;
 
-        serde_json::from_reader(file)
+        let project_config: ProjectConfig = serde_json::from_reader(file)
             .into_report()
             .change_context(ProjectConfigError::InvalidConfig)
-            .attach_lazy(|| "Error while parsing configuration file.")
+            .attach_lazy(|| "Error while parsing configuration file.")?;
+
+        project_config.validate()?;
+
+        Ok(project_config)
+    }
+
+    // Function to check the templates before they are used
+    fn validate(&self) -> Result<(), ProjectConfigError> {
+        let mut templates = vec![("worktree_path", &self.worktree_path)];
+        if let Some(pull_request) = &self.pull_request {
+            templates.push(("pull_request title", &pull_request.title));
+            templates.push(("pull_request body", &pull_request.body));
+        }
+        templates.into_iter().try_for_each(|(name, template)| {
+            Template::parse(template)
+                .change_context(ProjectConfigError::MalformedTemplate)
+                .attach_printable_lazy(|| format!("Invalid {} \"{}\"", name, template))
+                .map(|_| ())
+        })?;
+
+        self.branch_kinds.iter().try_for_each(|branch_kind| {
+            Template::parse(&branch_kind.formatter)
+                .change_context(ProjectConfigError::MalformedTemplate)
+                .attach_printable_lazy(|| {
+                    format!(
+                        "Invalid formatter \"{}\" for the {} branch kind",
+                        branch_kind.formatter, branch_kind.kind
+                    )
+                })
+                .map(|_| ())
+        })
+    }
+
+    // Function to find the configuration of a kind of branch
+    fn branch_kind(&self, kind: &str) -> Option<&BranchKind> {
+        self.branch_kinds
+            .iter()
+            .find(|branch_kind| branch_kind.kind == kind)
+    }
+
+    // Function to find the branch template of a kind of branch
+    pub fn formatter(&self, kind: &str) -> &str {
+        self.branch_kind(kind)
+            .map(|branch_kind| branch_kind.formatter.as_str())
+            .unwrap_or("feature/{id}-{name}")
+    }
+
+    // Function to find the default base of a kind of branch
+    pub fn base(&self, kind: &str) -> Option<&str> {
+        self.branch_kind(kind)
+            .and_then(|branch_kind| branch_kind.base.as_deref())
     }
 
     // Function to initialize the project configuration
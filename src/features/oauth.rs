@@ -0,0 +1,795 @@
+// OAuth 2.0 logins used by `gbc login`, the device authorization flow (RFC
+// 8628) and, for Jira Cloud which has no device flow, the authorization code
+// flow with a loopback redirect. Also refreshes the tokens they get.
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant, SystemTime};
+use std::{error::Error, fmt, fmt::Display};
+use url::Url;
+
+use crate::features::global_config::{Access, Credentials};
+use crate::secrets::SecretStore;
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// Atlassian API, the Jira Cloud sites are reached through it with OAuth tokens
+const ATLASSIAN_API_URL: &str = "https://api.atlassian.com";
+const DEFAULT_REDIRECT_PORT: u16 = 8765;
+
+// Errors that can occur during the OAuth flows
+#[derive(Debug)]
+pub enum OAuthError {
+    RequestFailed,
+    InvalidResponse,
+    AuthorizationDenied(String),
+    AuthorizationExpired,
+    NoRefreshToken,
+    MissingClientSecret,
+    CallbackFailed,
+    JiraSiteNotFound,
+}
+
+impl Display for OAuthError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OAuthError::RequestFailed => fmt.write_str("Authorization server request failed"),
+            OAuthError::InvalidResponse => fmt.write_str("Invalid authorization server response"),
+            OAuthError::AuthorizationDenied(error) => {
+                write!(fmt, "Authorization denied: {}", error)
+            }
+            OAuthError::AuthorizationExpired => {
+                fmt.write_str("The device code expired before the authorization")
+            }
+            OAuthError::NoRefreshToken => fmt.write_str("No refresh token to renew the access"),
+            OAuthError::MissingClientSecret => {
+                fmt.write_str("The client secret of the OAuth application is required")
+            }
+            OAuthError::CallbackFailed => fmt.write_str("Invalid authorization callback"),
+            OAuthError::JiraSiteNotFound => {
+                fmt.write_str("No single Jira site to save the credentials for")
+            }
+        }
+    }
+}
+
+impl Error for OAuthError {}
+
+// Authorization servers gbc knows the endpoints of
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Provider {
+    Github,
+    Gitlab,
+    Jira,
+}
+
+impl Provider {
+    fn default_auth_url(&self) -> &'static str {
+        match self {
+            Provider::Github => "https://github.com",
+            Provider::Gitlab => "https://gitlab.com",
+            Provider::Jira => "https://auth.atlassian.com",
+        }
+    }
+
+    // Device authorization (authorization for Jira) and token endpoints,
+    // relative to the server
+    fn endpoints(&self) -> (&'static str, &'static str) {
+        match self {
+            Provider::Github => ("/login/device/code", "/login/oauth/access_token"),
+            Provider::Gitlab => ("/oauth/authorize_device", "/oauth/token"),
+            Provider::Jira => ("/authorize", "/oauth/token"),
+        }
+    }
+
+    fn default_scope(&self) -> &'static str {
+        match self {
+            Provider::Github => "repo",
+            Provider::Gitlab => "api",
+            Provider::Jira => "read:jira-work write:jira-work read:jira-user offline_access",
+        }
+    }
+}
+
+// Options of `gbc login`, the provider defaults are used when missing
+pub struct LoginOptions {
+    pub client_id: String,
+    // Only for Jira, whose OAuth applications are confidential clients
+    pub client_secret: Option<String>,
+    pub auth_url: Option<String>,
+    pub url: Option<String>,
+    pub scope: Option<String>,
+    // Port of the loopback redirect of Jira, http://localhost:{port}/callback
+    pub redirect_port: Option<u16>,
+}
+
+impl LoginOptions {
+    fn auth_url(&self, provider: Provider) -> String {
+        self.auth_url
+            .as_deref()
+            .unwrap_or(provider.default_auth_url())
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+#[derive(Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+// Token endpoint answer, the errors come with a 400 from most servers and with
+// a 200 from GitHub
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+impl TokenResponse {
+    fn denied(self) -> Report<OAuthError> {
+        Report::new(OAuthError::AuthorizationDenied(
+            self.error
+                .unwrap_or_else(|| String::from("no access token")),
+        ))
+        .attach_printable(self.error_description.unwrap_or_default())
+    }
+}
+
+// Jira Cloud site an OAuth token gives access to
+#[derive(Deserialize)]
+struct JiraSite {
+    id: String,
+    url: String,
+}
+
+// POST a form and parse the JSON answer, error statuses included
+fn post_form<T: serde::de::DeserializeOwned>(
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<T, OAuthError> {
+    let response = match ureq::post(url)
+        .set("Accept", "application/json")
+        .send_form(form)
+    {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(ureq::Error::Transport(transport)) => {
+            return Err(Report::new(OAuthError::RequestFailed)
+                .attach_printable(format!("{}: {}", url, transport)))
+        }
+    };
+
+    response
+        .into_json()
+        .into_report()
+        .change_context(OAuthError::InvalidResponse)
+        .attach_printable_lazy(|| format!("Unexpected response from {}", url))
+}
+
+// Let the user authorize gbc in a browser. Returns the credentials to save.
+pub fn login(provider: Provider, options: LoginOptions) -> Result<Credentials, OAuthError> {
+    match provider {
+        Provider::Jira => authorization_code_flow(provider, options, |url| {
+            println!("Open {} to authorize gbc", url)
+        }),
+        _ => device_flow(provider, options),
+    }
+}
+
+// Run the device flow, the token endpoint is polled while the user enters the
+// code in a browser
+fn device_flow(provider: Provider, options: LoginOptions) -> Result<Credentials, OAuthError> {
+    let auth_url = options.auth_url(provider);
+    let (device_path, token_path) = provider.endpoints();
+    let token_url = format!("{}{}", auth_url, token_path);
+    let scope = options.scope.as_deref().unwrap_or(provider.default_scope());
+
+    let device: DeviceCode = post_form(
+        &format!("{}{}", auth_url, device_path),
+        &[("client_id", &options.client_id), ("scope", scope)],
+    )?;
+    match &device.verification_uri_complete {
+        Some(uri) => println!("Open {} to authorize gbc", uri),
+        None => println!(
+            "Open {} and enter the code {}",
+            device.verification_uri, device.user_code
+        ),
+    }
+
+    let expires_at = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = device.interval;
+    loop {
+        if Instant::now() >= expires_at {
+            return Err(Report::new(OAuthError::AuthorizationExpired));
+        }
+        std::thread::sleep(Duration::from_secs(interval));
+
+        let mut token: TokenResponse = post_form(
+            &token_url,
+            &[
+                ("grant_type", DEVICE_CODE_GRANT),
+                ("device_code", &device.device_code),
+                ("client_id", &options.client_id),
+            ],
+        )?;
+        match (token.access_token.take(), token.error.as_deref()) {
+            (Some(access_token), None) => {
+                // The API of GitLab is on the instance itself
+                let default_url = match provider {
+                    Provider::Github => String::from("https://api.github.com"),
+                    _ => auth_url,
+                };
+                return Ok(Credentials {
+                    url: options.url.unwrap_or(default_url),
+                    access: Access::OAuth {
+                        client_id: options.client_id,
+                        token_url,
+                        access_token,
+                        refresh_token: token.refresh_token.take(),
+                        client_secret: None,
+                    },
+                    store: SecretStore::default(),
+                });
+            }
+            (_, Some("authorization_pending")) => {}
+            (_, Some("slow_down")) => interval += 5,
+            (_, Some("expired_token")) => {
+                return Err(Report::new(OAuthError::AuthorizationExpired))
+            }
+            _ => return Err(token.denied()),
+        }
+    }
+}
+
+// Run the authorization code flow, the browser is redirected with the code to
+// a server listening on the loopback interface. The redirect URL must be the
+// callback URL of the OAuth application.
+fn authorization_code_flow(
+    provider: Provider,
+    options: LoginOptions,
+    open: impl FnOnce(&str),
+) -> Result<Credentials, OAuthError> {
+    let client_secret = options
+        .client_secret
+        .clone()
+        .ok_or_else(|| Report::new(OAuthError::MissingClientSecret))?;
+    let auth_url = options.auth_url(provider);
+    let (authorize_path, token_path) = provider.endpoints();
+    let token_url = format!("{}{}", auth_url, token_path);
+    let scope = options.scope.as_deref().unwrap_or(provider.default_scope());
+
+    let port = options.redirect_port.unwrap_or(DEFAULT_REDIRECT_PORT);
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .into_report()
+        .change_context(OAuthError::CallbackFailed)
+        .attach_printable_lazy(|| format!("Could not listen on the port {}", port))?;
+    let port = listener
+        .local_addr()
+        .into_report()
+        .change_context(OAuthError::CallbackFailed)?
+        .port();
+    let redirect_uri = format!("http://localhost:{}/callback", port);
+    let state = random_state();
+
+    let authorize_url = Url::parse_with_params(
+        &format!("{}{}", auth_url, authorize_path),
+        &[
+            ("audience", "api.atlassian.com"),
+            ("client_id", &options.client_id),
+            ("scope", scope),
+            ("redirect_uri", &redirect_uri),
+            ("state", &state),
+            ("response_type", "code"),
+            ("prompt", "consent"),
+        ],
+    )
+    .into_report()
+    .change_context(OAuthError::RequestFailed)
+    .attach_printable_lazy(|| format!("Invalid authorization server {}", auth_url))?;
+    open(authorize_url.as_str());
+
+    let code = wait_for_code(&listener, &state)?;
+    let token: TokenResponse = post_form(
+        &token_url,
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", &options.client_id),
+            ("client_secret", &client_secret),
+            ("code", &code),
+            ("redirect_uri", &redirect_uri),
+        ],
+    )?;
+    let Some(access_token) = token.access_token.clone() else {
+        return Err(token.denied());
+    };
+    let url = match options.url {
+        Some(url) => url,
+        None => jira_site_url(ATLASSIAN_API_URL, &access_token)?,
+    };
+
+    Ok(Credentials {
+        url,
+        access: Access::OAuth {
+            client_id: options.client_id,
+            token_url,
+            access_token,
+            refresh_token: token.refresh_token,
+            client_secret: Some(client_secret),
+        },
+        store: SecretStore::default(),
+    })
+}
+
+// Unguessable value tying the callback to this login, the hasher keys are
+// random for each process
+fn random_state() -> String {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+
+    format!("{:016x}", hasher.finish())
+}
+
+// Answer the browser redirected to the loopback server and return the code,
+// the other requests (favicon...) are answered with a 404
+fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String, OAuthError> {
+    for stream in listener.incoming() {
+        let mut stream = stream
+            .into_report()
+            .change_context(OAuthError::CallbackFailed)?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader
+            .read_line(&mut request_line)
+            .into_report()
+            .change_context(OAuthError::CallbackFailed)?;
+        // The headers are read so the browser gets the answer
+        let mut header = String::new();
+        while reader.read_line(&mut header).is_ok_and(|read| read > 2) {
+            header.clear();
+        }
+
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let callback = Url::parse(&format!("http://localhost{}", target))
+            .ok()
+            .filter(|url| url.path() == "/callback");
+        let Some(callback) = callback else {
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .ok();
+            continue;
+        };
+
+        let params: HashMap<String, String> = callback.query_pairs().into_owned().collect();
+        let result = match (params.get("code"), params.get("error")) {
+            _ if params.get("state").map(String::as_str) != Some(state) => {
+                Err(Report::new(OAuthError::CallbackFailed)
+                    .attach_printable("The state does not match the authorization request"))
+            }
+            (Some(code), None) => Ok(code.clone()),
+            (_, error) => Err(Report::new(OAuthError::AuthorizationDenied(
+                error.cloned().unwrap_or_else(|| String::from("no code")),
+            ))
+            .attach_printable(params.get("error_description").cloned().unwrap_or_default())),
+        };
+        let message = match &result {
+            Ok(_) => "gbc is authorized, you can close this page",
+            Err(_) => "gbc is not authorized, see the terminal",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .ok();
+
+        return result;
+    }
+
+    Err(Report::new(OAuthError::CallbackFailed))
+}
+
+// API URL of the Jira Cloud site the token gives access to, the site has to
+// be given when there are several
+fn jira_site_url(api_url: &str, access_token: &str) -> Result<String, OAuthError> {
+    let url = format!("{}/oauth/token/accessible-resources", api_url);
+    let sites: Vec<JiraSite> = ureq::get(&url)
+        .set("Accept", "application/json")
+        .set("Authorization", &format!("Bearer {}", access_token))
+        .call()
+        .into_report()
+        .change_context(OAuthError::RequestFailed)
+        .attach_printable_lazy(|| format!("Could not list the Jira sites from {}", url))?
+        .into_json()
+        .into_report()
+        .change_context(OAuthError::InvalidResponse)?;
+
+    let site_url = |site: &JiraSite| format!("{}/ex/jira/{}", api_url, site.id);
+    match sites.as_slice() {
+        [site] => Ok(site_url(site)),
+        sites => Err(
+            Report::new(OAuthError::JiraSiteNotFound).attach_printable(format!(
+                "Give the API URL of the site with --url:{}",
+                sites
+                    .iter()
+                    .map(|site| format!("\n  {}: {}", site.url, site_url(site)))
+                    .collect::<String>()
+            )),
+        ),
+    }
+}
+
+// Get new tokens with the refresh token of OAuth credentials
+pub fn refresh(credentials: &Credentials) -> Result<Credentials, OAuthError> {
+    let Access::OAuth {
+        client_id,
+        token_url,
+        refresh_token: Some(refresh_token),
+        client_secret,
+        ..
+    } = &credentials.access
+    else {
+        return Err(Report::new(OAuthError::NoRefreshToken)
+            .attach_printable(format!("Run 'gbc login' again for {}", credentials.url)));
+    };
+
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.as_str()),
+        ("client_id", client_id.as_str()),
+    ];
+    if let Some(client_secret) = client_secret {
+        form.push(("client_secret", client_secret));
+    }
+    let token: TokenResponse = post_form(token_url, &form)?;
+    let access_token = token.access_token.ok_or_else(|| {
+        Report::new(OAuthError::AuthorizationDenied(
+            token
+                .error
+                .unwrap_or_else(|| String::from("no access token")),
+        ))
+        .attach_printable(format!("Run 'gbc login' again for {}", credentials.url))
+    })?;
+
+    Ok(Credentials {
+        access: Access::OAuth {
+            client_id: client_id.clone(),
+            token_url: token_url.clone(),
+            access_token,
+            // Servers without refresh token rotation keep the same one
+            refresh_token: token.refresh_token.or_else(|| Some(refresh_token.clone())),
+            client_secret: client_secret.clone(),
+        },
+        ..credentials.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    fn options(server: &Server) -> LoginOptions {
+        LoginOptions {
+            client_id: String::from("client"),
+            client_secret: None,
+            auth_url: Some(server.url()),
+            url: None,
+            scope: None,
+            redirect_port: None,
+        }
+    }
+
+    fn device_code(server: &mut Server) -> mockito::Mock {
+        server
+            .mock("POST", "/login/device/code")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("client_id".into(), "client".into()),
+                Matcher::UrlEncoded("scope".into(), "repo".into()),
+            ]))
+            .with_body(
+                json!({
+                    "device_code": "device",
+                    "user_code": "ABCD-1234",
+                    "verification_uri": "https://github.com/login/device",
+                    "expires_in": 60,
+                    "interval": 0,
+                })
+                .to_string(),
+            )
+            .create()
+    }
+
+    fn token_answer(server: &mut Server, status: usize, body: serde_json::Value) -> mockito::Mock {
+        server
+            .mock("POST", "/login/oauth/access_token")
+            .match_body(Matcher::UrlEncoded("device_code".into(), "device".into()))
+            .with_status(status)
+            .with_body(body.to_string())
+            .expect(1)
+            .create()
+    }
+
+    fn oauth_credentials(token_url: &str, refresh_token: &str) -> Credentials {
+        Credentials {
+            url: String::from("https://api.github.com"),
+            access: Access::OAuth {
+                client_id: String::from("client"),
+                token_url: token_url.to_string(),
+                access_token: String::from("expired"),
+                refresh_token: Some(refresh_token.to_string()),
+                client_secret: None,
+            },
+            store: SecretStore::default(),
+        }
+    }
+
+    #[test]
+    fn device_flow_polls_until_authorized() {
+        let mut server = Server::new();
+        device_code(&mut server);
+        let pending = token_answer(
+            &mut server,
+            200,
+            json!({ "error": "authorization_pending" }),
+        );
+        let slow_down = token_answer(&mut server, 200, json!({ "error": "slow_down" }));
+        let success = token_answer(
+            &mut server,
+            200,
+            json!({ "access_token": "access", "refresh_token": "refresh" }),
+        );
+
+        let credentials = login(Provider::Github, options(&server)).unwrap();
+
+        pending.assert();
+        slow_down.assert();
+        success.assert();
+        assert_eq!(credentials.url, "https://api.github.com");
+        assert!(matches!(
+            credentials.access,
+            Access::OAuth { access_token, refresh_token: Some(refresh_token), token_url, .. }
+                if access_token == "access"
+                    && refresh_token == "refresh"
+                    && token_url == format!("{}/login/oauth/access_token", server.url())
+        ));
+    }
+
+    #[test]
+    fn device_flow_stops_on_an_expired_code() {
+        let mut server = Server::new();
+        device_code(&mut server);
+        token_answer(&mut server, 400, json!({ "error": "expired_token" }));
+
+        let error = login(Provider::Github, options(&server)).unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            OAuthError::AuthorizationExpired
+        ));
+    }
+
+    #[test]
+    fn device_flow_reports_the_server_error() {
+        let mut server = Server::new();
+        device_code(&mut server);
+        token_answer(
+            &mut server,
+            400,
+            json!({ "error": "access_denied", "error_description": "The user said no" }),
+        );
+
+        let error = login(Provider::Github, options(&server)).unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            OAuthError::AuthorizationDenied(error) if error == "access_denied"
+        ));
+        assert!(format!("{:?}", error).contains("The user said no"));
+    }
+
+    // Plays the browser coming back to the loopback server
+    fn browser(code: &'static str, state: Option<&'static str>) -> impl FnOnce(&str) {
+        move |authorize_url| {
+            let authorize_url = Url::parse(authorize_url).unwrap();
+            let params: HashMap<String, String> =
+                authorize_url.query_pairs().into_owned().collect();
+            let mut callback = Url::parse(&params["redirect_uri"]).unwrap();
+            callback
+                .query_pairs_mut()
+                .append_pair("code", code)
+                .append_pair("state", state.unwrap_or(&params["state"]));
+
+            std::thread::spawn(move || {
+                ureq::get("http://localhost/favicon.ico").call().ok();
+                ureq::get(callback.as_str()).call().ok();
+            });
+        }
+    }
+
+    fn jira_options(server: &Server) -> LoginOptions {
+        LoginOptions {
+            client_secret: Some(String::from("secret")),
+            url: Some(String::from("https://api.atlassian.com/ex/jira/cloud")),
+            redirect_port: Some(0),
+            ..options(server)
+        }
+    }
+
+    #[test]
+    fn authorization_code_flow_exchanges_the_code() {
+        let mut server = Server::new();
+        let token = server
+            .mock("POST", "/oauth/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("grant_type".into(), "authorization_code".into()),
+                Matcher::UrlEncoded("code".into(), "code".into()),
+                Matcher::UrlEncoded("client_secret".into(), "secret".into()),
+            ]))
+            .with_body(json!({ "access_token": "access", "refresh_token": "refresh" }).to_string())
+            .create();
+
+        let credentials =
+            authorization_code_flow(Provider::Jira, jira_options(&server), browser("code", None))
+                .unwrap();
+
+        token.assert();
+        assert_eq!(credentials.url, "https://api.atlassian.com/ex/jira/cloud");
+        assert!(matches!(
+            credentials.access,
+            Access::OAuth { access_token, client_secret: Some(client_secret), .. }
+                if access_token == "access" && client_secret == "secret"
+        ));
+    }
+
+    #[test]
+    fn authorization_code_flow_checks_the_state() {
+        let server = Server::new();
+
+        let error = authorization_code_flow(
+            Provider::Jira,
+            jira_options(&server),
+            browser("code", Some("forged")),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            OAuthError::CallbackFailed
+        ));
+    }
+
+    #[test]
+    fn authorization_code_flow_needs_the_client_secret() {
+        let server = Server::new();
+        let options = LoginOptions {
+            client_secret: None,
+            ..jira_options(&server)
+        };
+
+        let error = login(Provider::Jira, options).unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            OAuthError::MissingClientSecret
+        ));
+    }
+
+    #[test]
+    fn jira_site_url_uses_the_single_site() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/oauth/token/accessible-resources")
+            .match_header("Authorization", "Bearer access")
+            .with_body(
+                json!([{ "id": "cloud-1", "url": "https://acme.atlassian.net" }]).to_string(),
+            )
+            .create();
+
+        let url = jira_site_url(&server.url(), "access").unwrap();
+
+        assert_eq!(url, format!("{}/ex/jira/cloud-1", server.url()));
+    }
+
+    #[test]
+    fn jira_site_url_lists_the_sites_to_choose_from() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/oauth/token/accessible-resources")
+            .with_body(
+                json!([
+                    { "id": "cloud-1", "url": "https://acme.atlassian.net" },
+                    { "id": "cloud-2", "url": "https://other.atlassian.net" },
+                ])
+                .to_string(),
+            )
+            .create();
+
+        let error = jira_site_url(&server.url(), "access").unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            OAuthError::JiraSiteNotFound
+        ));
+        assert!(format!("{:?}", error).contains("https://other.atlassian.net"));
+    }
+
+    #[test]
+    fn refresh_keeps_the_refresh_token_when_not_rotated() {
+        let mut server = Server::new();
+        let token = server
+            .mock("POST", "/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("grant_type".into(), "refresh_token".into()),
+                Matcher::UrlEncoded("refresh_token".into(), "old".into()),
+            ]))
+            .with_body(json!({ "access_token": "new" }).to_string())
+            .create();
+        let credentials = oauth_credentials(&format!("{}/token", server.url()), "old");
+
+        let refreshed = refresh(&credentials).unwrap();
+
+        token.assert();
+        assert!(matches!(
+            refreshed.access,
+            Access::OAuth { access_token, refresh_token: Some(refresh_token), .. }
+                if access_token == "new" && refresh_token == "old"
+        ));
+    }
+
+    #[test]
+    fn refresh_uses_the_rotated_refresh_token() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/token")
+            .with_body(json!({ "access_token": "new", "refresh_token": "rotated" }).to_string())
+            .create();
+        let credentials = oauth_credentials(&format!("{}/token", server.url()), "old");
+
+        let refreshed = refresh(&credentials).unwrap();
+
+        assert!(matches!(
+            refreshed.access,
+            Access::OAuth { refresh_token: Some(refresh_token), .. } if refresh_token == "rotated"
+        ));
+    }
+
+    #[test]
+    fn refresh_reports_a_rejected_refresh_token() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/token")
+            .with_status(400)
+            .with_body(json!({ "error": "invalid_grant" }).to_string())
+            .create();
+        let credentials = oauth_credentials(&format!("{}/token", server.url()), "old");
+
+        let error = refresh(&credentials).unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            OAuthError::AuthorizationDenied(error) if error == "invalid_grant"
+        ));
+    }
+}
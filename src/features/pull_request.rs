@@ -0,0 +1,456 @@
+// Open draft pull requests on GitHub and merge requests on GitLab for the
+// branches created by gbc
+
+use error_stack::{Report, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+use std::{error::Error, fmt::Display};
+
+use crate::drivers::{github, gitlab, http, DriverError};
+use crate::features::global_config::Credentials;
+
+// Errors that can occur when opening a pull request
+#[derive(Debug)]
+pub enum PullRequestError {
+    NotConfigured,
+    MissingCredentials,
+    UnsupportedAccess,
+    Unauthorized,
+    RequestFailed,
+}
+
+impl Display for PullRequestError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PullRequestError::NotConfigured => {
+                fmt.write_str("Pull requests are not configured for this project")
+            }
+            PullRequestError::MissingCredentials => {
+                fmt.write_str("Missing credentials for the pull requests")
+            }
+            PullRequestError::UnsupportedAccess => {
+                fmt.write_str("Pull requests require a token access")
+            }
+            PullRequestError::Unauthorized => {
+                fmt.write_str("The credentials were rejected by the forge")
+            }
+            PullRequestError::RequestFailed => fmt.write_str("Could not open the pull request"),
+        }
+    }
+}
+
+impl Error for PullRequestError {}
+
+// Forge hosting the repository
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum Provider {
+    Github,
+    Gitlab,
+}
+
+impl Provider {
+    fn default_url(&self) -> &'static str {
+        match self {
+            Provider::Github => "https://api.github.com",
+            Provider::Gitlab => "https://gitlab.com",
+        }
+    }
+}
+
+// Pull request settings of the project configuration
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PullRequestConfig {
+    pub provider: Provider,
+    // API base URL, the public GitHub API or gitlab.com when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    // Repository in the owner/repo form, or the GitLab project path
+    pub repository: String,
+    // Branch the pull requests target, the base of the branch kind or the
+    // default branch of the repository when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default = "default_title")]
+    pub title: String,
+    #[serde(default = "default_body")]
+    pub body: String,
+    // Open a draft pull request for every new branch, not only with --pr
+    #[serde(default)]
+    pub auto: bool,
+}
+
+fn default_title() -> String {
+    String::from("{title}")
+}
+
+fn default_body() -> String {
+    String::from("Issue: {url}\n\n{description}")
+}
+
+impl PullRequestConfig {
+    pub fn url(&self) -> String {
+        self.url
+            .as_deref()
+            .unwrap_or(self.provider.default_url())
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+// Draft pull request to open
+pub struct PullRequest<'a> {
+    pub source: &'a str,
+    pub target: Option<&'a str>,
+    pub title: String,
+    pub body: String,
+}
+
+// Open a draft pull request and return its web URL
+pub fn open(
+    config: &PullRequestConfig,
+    credentials: Option<&Credentials>,
+    pull_request: &PullRequest,
+) -> Result<String, PullRequestError> {
+    let url = config.url();
+    let Some(credentials) = credentials else {
+        return Err(
+            Report::new(PullRequestError::MissingCredentials).attach_printable(format!(
+                "No credentials found for {}, please run 'gbc global-config add-credentials'",
+                url
+            )),
+        );
+    };
+
+    match config.provider {
+        Provider::Github => match github::token(&credentials.access) {
+            Some(token) => github_pull_request(&url, &config.repository, token, pull_request),
+            None => Err(Report::new(PullRequestError::UnsupportedAccess)),
+        },
+        Provider::Gitlab => match gitlab::authorization(&credentials.access) {
+            Some(authorization) => {
+                gitlab_merge_request(&url, &config.repository, authorization, pull_request)
+            }
+            None => Err(Report::new(PullRequestError::UnsupportedAccess)),
+        },
+    }
+}
+
+// Send a request to the forge, a 401 lets the caller refresh an OAuth token
+fn send(
+    request: ureq::Request,
+    body: Option<&serde_json::Value>,
+) -> Result<ureq::Response, PullRequestError> {
+    http::send(request, body).map_err(|e| {
+        let context = match e.current_context() {
+            DriverError::Unauthorized => PullRequestError::Unauthorized,
+            _ => PullRequestError::RequestFailed,
+        };
+        e.change_context(context)
+    })
+}
+
+#[derive(Deserialize)]
+struct GithubRepository {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct GithubPullRequest {
+    html_url: String,
+}
+
+fn github_pull_request(
+    url: &str,
+    repository: &str,
+    token: &str,
+    pull_request: &PullRequest,
+) -> Result<String, PullRequestError> {
+    let request = |method: &str, url: &str| github::request(method, url, token);
+    let endpoint = format!("{}/repos/{}", url, repository);
+
+    let target = match pull_request.target {
+        Some(target) => target.to_string(),
+        None => {
+            let response = send(request("GET", &endpoint), None)?;
+            http::read_json::<GithubRepository>(response)
+                .change_context(PullRequestError::RequestFailed)?
+                .default_branch
+        }
+    };
+    let body = json!({
+        "title": pull_request.title,
+        "body": pull_request.body,
+        "head": pull_request.source,
+        "base": target,
+        "draft": true,
+    });
+    let response = send(request("POST", &format!("{}/pulls", endpoint)), Some(&body))?;
+
+    http::read_json::<GithubPullRequest>(response)
+        .change_context(PullRequestError::RequestFailed)
+        .map(|pull_request| pull_request.html_url)
+}
+
+#[derive(Deserialize)]
+struct GitlabProject {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct GitlabMergeRequest {
+    web_url: String,
+}
+
+fn gitlab_merge_request(
+    url: &str,
+    project: &str,
+    authorization: (&str, String),
+    pull_request: &PullRequest,
+) -> Result<String, PullRequestError> {
+    let request =
+        |method: &str, url: &str| ureq::request(method, url).set(authorization.0, &authorization.1);
+    let endpoint = format!("{}/api/v4/projects/{}", url, project.replace('/', "%2F"));
+
+    let target = match pull_request.target {
+        Some(target) => target.to_string(),
+        None => {
+            let response = send(request("GET", &endpoint), None)?;
+            http::read_json::<GitlabProject>(response)
+                .change_context(PullRequestError::RequestFailed)?
+                .default_branch
+        }
+    };
+    // Merge requests are drafts when their title starts with "Draft:"
+    let body = json!({
+        "title": format!("Draft: {}", pull_request.title),
+        "description": pull_request.body,
+        "source_branch": pull_request.source,
+        "target_branch": target,
+    });
+    let response = send(
+        request("POST", &format!("{}/merge_requests", endpoint)),
+        Some(&body),
+    )?;
+
+    http::read_json::<GitlabMergeRequest>(response)
+        .change_context(PullRequestError::RequestFailed)
+        .map(|merge_request| merge_request.web_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::global_config::Access;
+    use crate::secrets::SecretStore;
+    use mockito::{Matcher, Server};
+
+    fn config(provider: Provider, server: &Server, repository: &str) -> PullRequestConfig {
+        PullRequestConfig {
+            provider,
+            url: Some(server.url()),
+            repository: repository.to_string(),
+            target: None,
+            title: default_title(),
+            body: default_body(),
+            auto: false,
+        }
+    }
+
+    fn credentials(server: &Server, access: Access) -> Credentials {
+        Credentials {
+            url: server.url(),
+            access,
+            store: SecretStore::Plaintext,
+        }
+    }
+
+    fn token() -> Access {
+        Access::Token {
+            token: String::from("token"),
+        }
+    }
+
+    fn oauth() -> Access {
+        Access::OAuth {
+            client_id: String::from("client"),
+            token_url: String::from("https://gitlab.com/oauth/token"),
+            access_token: String::from("access"),
+            refresh_token: None,
+            client_secret: None,
+        }
+    }
+
+    fn pull_request(target: Option<&str>) -> PullRequest<'_> {
+        PullRequest {
+            source: "feature/42-fix-login",
+            target,
+            title: String::from("Fix login"),
+            body: String::from("Issue: 42"),
+        }
+    }
+
+    #[test]
+    fn github_opens_a_draft_on_the_default_branch() {
+        let mut server = Server::new();
+        let repository = server
+            .mock("GET", "/repos/owner/repo")
+            .match_header("Accept", "application/vnd.github+json")
+            .match_header("X-GitHub-Api-Version", "2022-11-28")
+            .match_header("Authorization", "Bearer token")
+            .with_body(r#"{ "default_branch": "trunk" }"#)
+            .create();
+        let pulls = server
+            .mock("POST", "/repos/owner/repo/pulls")
+            .match_header("Authorization", "Bearer token")
+            .match_body(Matcher::Json(json!({
+                "title": "Fix login",
+                "body": "Issue: 42",
+                "head": "feature/42-fix-login",
+                "base": "trunk",
+                "draft": true,
+            })))
+            .with_status(201)
+            .with_body(r#"{ "html_url": "https://github.com/owner/repo/pull/7" }"#)
+            .create();
+
+        let url = open(
+            &config(Provider::Github, &server, "owner/repo"),
+            Some(&credentials(&server, token())),
+            &pull_request(None),
+        )
+        .unwrap();
+
+        repository.assert();
+        pulls.assert();
+        assert_eq!(url, "https://github.com/owner/repo/pull/7");
+    }
+
+    #[test]
+    fn github_targets_the_given_branch() {
+        let mut server = Server::new();
+        let repository = server.mock("GET", "/repos/owner/repo").expect(0).create();
+        server
+            .mock("POST", "/repos/owner/repo/pulls")
+            .match_body(Matcher::PartialJson(json!({ "base": "develop" })))
+            .with_status(201)
+            .with_body(r#"{ "html_url": "https://github.com/owner/repo/pull/7" }"#)
+            .create();
+
+        open(
+            &config(Provider::Github, &server, "owner/repo"),
+            Some(&credentials(&server, token())),
+            &pull_request(Some("develop")),
+        )
+        .unwrap();
+
+        repository.assert();
+    }
+
+    #[test]
+    fn github_reports_a_rejected_token() {
+        let mut server = Server::new();
+        server
+            .mock("POST", "/repos/owner/repo/pulls")
+            .with_status(401)
+            .with_body(r#"{ "message": "Bad credentials" }"#)
+            .create();
+
+        let error = open(
+            &config(Provider::Github, &server, "owner/repo"),
+            Some(&credentials(&server, token())),
+            &pull_request(Some("main")),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            PullRequestError::Unauthorized
+        ));
+        assert!(format!("{:?}", error).contains("Bad credentials"));
+    }
+
+    #[test]
+    fn gitlab_opens_a_draft_with_a_private_token() {
+        let mut server = Server::new();
+        let project = server
+            .mock("GET", "/api/v4/projects/group%2Fproject")
+            .match_header("PRIVATE-TOKEN", "token")
+            .match_header("Authorization", Matcher::Missing)
+            .with_body(r#"{ "default_branch": "main" }"#)
+            .create();
+        let merge_requests = server
+            .mock("POST", "/api/v4/projects/group%2Fproject/merge_requests")
+            .match_header("PRIVATE-TOKEN", "token")
+            .match_body(Matcher::Json(json!({
+                "title": "Draft: Fix login",
+                "description": "Issue: 42",
+                "source_branch": "feature/42-fix-login",
+                "target_branch": "main",
+            })))
+            .with_status(201)
+            .with_body(r#"{ "web_url": "https://gitlab.com/group/project/-/merge_requests/3" }"#)
+            .create();
+
+        let url = open(
+            &config(Provider::Gitlab, &server, "group/project"),
+            Some(&credentials(&server, token())),
+            &pull_request(None),
+        )
+        .unwrap();
+
+        project.assert();
+        merge_requests.assert();
+        assert_eq!(url, "https://gitlab.com/group/project/-/merge_requests/3");
+    }
+
+    #[test]
+    fn gitlab_sends_oauth_tokens_as_bearer() {
+        let mut server = Server::new();
+        let merge_requests = server
+            .mock("POST", "/api/v4/projects/group%2Fproject/merge_requests")
+            .match_header("Authorization", "Bearer access")
+            .match_header("PRIVATE-TOKEN", Matcher::Missing)
+            .with_status(201)
+            .with_body(r#"{ "web_url": "https://gitlab.com/group/project/-/merge_requests/3" }"#)
+            .create();
+
+        open(
+            &config(Provider::Gitlab, &server, "group/project"),
+            Some(&credentials(&server, oauth())),
+            &pull_request(Some("main")),
+        )
+        .unwrap();
+
+        merge_requests.assert();
+    }
+
+    #[test]
+    fn requires_token_credentials() {
+        let server = Server::new();
+        let config = config(Provider::Github, &server, "owner/repo");
+
+        let missing = open(&config, None, &pull_request(None)).unwrap_err();
+        let basic = open(
+            &config,
+            Some(&credentials(
+                &server,
+                Access::Basic {
+                    username: String::from("jdoe"),
+                    password: String::from("hunter2"),
+                },
+            )),
+            &pull_request(None),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            missing.current_context(),
+            PullRequestError::MissingCredentials
+        ));
+        assert!(matches!(
+            basic.current_context(),
+            PullRequestError::UnsupportedAccess
+        ));
+    }
+}
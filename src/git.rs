@@ -1,18 +1,61 @@
 // This is a git adapter for the git2 crate. it is used to create a git branch
 // in the current repository
 
-use error_stack::{Report, Result};
-use git2::Repository;
+use error_stack::{Report, Result, ResultExt};
+use git2::{BranchType, Cred, CredentialType, RemoteCallbacks, Repository};
+use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{error::Error, fmt::Display};
 
+const WORKTREE_PREFIX: &str = "gbc-";
+
+// Issue a branch was created for, kept in the branch git config as
+// branch.<name>.gbc-issue, branch.<name>.gbc-url and branch.<name>.gbc-driver
+#[derive(Debug)]
+pub struct IssueLink {
+    pub id: String,
+    // Issue tracker URL
+    pub url: String,
+    pub driver: String,
+}
+
+// What to do when the issue already has a branch
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExistingBranchAction {
+    /// Checkout the existing branch
+    Checkout,
+    /// Create a new branch with a numbered suffix
+    Suffix,
+    /// Stop without creating any branch
+    Abort,
+}
+
+// What to do with the uncommitted changes when switching branches
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, clap::ValueEnum)]
+pub enum DirtyPolicy {
+    /// Carry the changes over to the new branch, unless they conflict with it
+    #[default]
+    Carry,
+    /// Stash the changes and re-apply them on the new branch
+    Stash,
+    /// Refuse to switch branches with uncommitted changes
+    Refuse,
+}
+
 // Errors that can occur when creating a branch
 #[derive(Debug)]
 pub enum BranchError {
     NotAGitRepository,
     BranchAlreadyExists,
+    StartPointNotFound(String),
+    DetachedHead,
+    DirtyWorktree,
     BranchCreationError(git2::Error),
+    RemoteError(git2::Error),
+    WorktreeError(git2::Error),
+    ConfigError(git2::Error),
+    IssueNotLinked(String),
 }
 
 impl Display for BranchError {
@@ -20,7 +63,20 @@ impl Display for BranchError {
         match self {
             BranchError::NotAGitRepository => fmt.write_str("Not a git repository"),
             BranchError::BranchAlreadyExists => fmt.write_str("Branch already exists"),
+            BranchError::StartPointNotFound(start_point) => {
+                write!(fmt, "Start point {} not found", start_point)
+            }
+            BranchError::DetachedHead => fmt.write_str("HEAD is not on a branch"),
+            BranchError::DirtyWorktree => {
+                fmt.write_str("Uncommitted changes prevent switching branches")
+            }
             BranchError::BranchCreationError(e) => write!(fmt, "Error creating branch: {}", e),
+            BranchError::RemoteError(e) => write!(fmt, "Error talking to the remote: {}", e),
+            BranchError::WorktreeError(e) => write!(fmt, "Error handling worktree: {}", e),
+            BranchError::ConfigError(e) => write!(fmt, "Error reading git config: {}", e),
+            BranchError::IssueNotLinked(branch) => {
+                write!(fmt, "Branch {} is not linked to an issue", branch)
+            }
         }
     }
 }
@@ -32,19 +88,96 @@ fn git_repository_open_from_workdir(path: PathBuf) -> Result<Repository, BranchE
     Repository::open(path).map_err(|_| Report::new(BranchError::NotAGitRepository))
 }
 
-// Create a git branch in the current repository
-pub fn branch_create(repo_path: Option<PathBuf>, branch_name: String) -> Result<(), BranchError> {
+// Name of the git user, from the repository or the global configuration
+pub fn user_name(repo_path: Option<PathBuf>) -> Option<String> {
     let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
-    let repo = git_repository_open_from_workdir(path)?;
-    let head = repo
-        .head()
-        .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?;
-    let head_commit = head
-        .peel_to_commit()
-        .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?;
+    let config = match Repository::open(path) {
+        Ok(repo) => repo.config(),
+        Err(_) => git2::Config::open_default(),
+    };
+
+    config.ok()?.get_string("user.name").ok()
+}
+
+// Name of the repository directory
+pub fn repository_name(repo_path: Option<PathBuf>) -> Option<String> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = Repository::open(path).ok()?;
+    let workdir = repo.workdir()?.canonicalize().ok()?;
+
+    workdir.file_name()?.to_str().map(String::from)
+}
+
+// Name of the checked out branch
+fn head_branch(repo: &Repository) -> Result<String, BranchError> {
+    repo.head()
+        .ok()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.shorthand().map(String::from))
+        .ok_or_else(|| Report::new(BranchError::DetachedHead))
+}
+
+// Find the commit of a start point, a local branch (main), a remote-tracking
+// branch (origin/develop) or any other revision (a tag or a commit)
+fn start_point_commit<'a>(
+    repo: &'a Repository,
+    start_point: &str,
+) -> Result<git2::Commit<'a>, BranchError> {
+    let not_found = || Report::new(BranchError::StartPointNotFound(start_point.to_string()));
+    let reference = repo
+        .find_branch(start_point, BranchType::Local)
+        .or_else(|_| repo.find_branch(start_point, BranchType::Remote))
+        .map(|branch| branch.into_reference());
+
+    match reference {
+        Ok(reference) => reference.peel_to_commit().map_err(|_| not_found()),
+        Err(_) => repo
+            .revparse_single(start_point)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| {
+                not_found().attach_printable(format!(
+                    "{} is neither a local branch, a remote-tracking branch nor a revision",
+                    start_point
+                ))
+            }),
+    }
+}
+
+// Create a git branch in the current repository, from the start point or from
+// HEAD when there is none, and switch to it
+pub fn branch_create(
+    repo_path: Option<PathBuf>,
+    branch_name: String,
+    start_point: Option<&str>,
+    dirty_policy: DirtyPolicy,
+) -> Result<(), BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let mut repo = git_repository_open_from_workdir(path)?;
+    let branch_ref_name = create(&repo, &branch_name, start_point)?;
+
+    // Do not leave the new branch behind when we could not switch to it
+    checkout(&mut repo, &branch_ref_name, dirty_policy).inspect_err(|_| {
+        if let Ok(mut reference) = repo.find_reference(&branch_ref_name) {
+            reference.delete().ok();
+        }
+    })
+}
 
+// Create the branch without switching to it, returns its reference name
+fn create(
+    repo: &Repository,
+    branch_name: &str,
+    start_point: Option<&str>,
+) -> Result<String, BranchError> {
+    let start_commit = match start_point {
+        Some(start_point) => start_point_commit(repo, start_point)?,
+        None => repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?,
+    };
     let branch = repo
-        .branch(&branch_name, &head_commit, false)
+        .branch(branch_name, &start_commit, false)
         .map_err(|e| {
             if e.code() == git2::ErrorCode::Exists {
                 Report::new(BranchError::BranchAlreadyExists)
@@ -52,15 +185,996 @@ pub fn branch_create(repo_path: Option<PathBuf>, branch_name: String) -> Result<
                 Report::new(BranchError::BranchCreationError(e))
             }
         })?;
-    let branch_ref = branch.into_reference();
-    let branch_ref_name = branch_ref.name().ok_or_else(|| {
-        Report::new(BranchError::BranchCreationError(git2::Error::from_str(
-            "Branch name is not valid UTF-8",
-        )))
-    })?;
+
+    branch_ref_name(branch)
+}
+
+fn branch_ref_name(branch: git2::Branch) -> Result<String, BranchError> {
+    branch
+        .into_reference()
+        .name()
+        .map(String::from)
+        .ok_or_else(|| {
+            Report::new(BranchError::BranchCreationError(git2::Error::from_str(
+                "Branch name is not valid UTF-8",
+            )))
+        })
+}
+
+// Tracked files with uncommitted changes
+fn dirty_files(repo: &Repository) -> Result<Vec<String>, BranchError> {
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?;
+
+    Ok(statuses
+        .iter()
+        .filter_map(|entry| entry.path().map(String::from))
+        .collect())
+}
+
+fn dirty_worktree_error(files: &[String]) -> Report<BranchError> {
+    Report::new(BranchError::DirtyWorktree).attach_printable(format!(
+        "Files with uncommitted changes:\n  {}",
+        files.join("\n  ")
+    ))
+}
+
+// Update the working directory to the branch and move HEAD to it, the
+// uncommitted changes are handled according to the dirty policy
+fn checkout(
+    repo: &mut Repository,
+    branch_ref_name: &str,
+    dirty_policy: DirtyPolicy,
+) -> Result<(), BranchError> {
+    let dirty = dirty_files(repo)?;
+    let stashed = match dirty_policy {
+        _ if dirty.is_empty() => false,
+        DirtyPolicy::Refuse => return Err(dirty_worktree_error(&dirty)),
+        DirtyPolicy::Carry => false,
+        DirtyPolicy::Stash => {
+            let signature = repo
+                .signature()
+                .or_else(|_| git2::Signature::now("gbc", "gbc@localhost"))
+                .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?;
+            repo.stash_save(&signature, "gbc: switching branches", None)
+                .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?;
+            true
+        }
+    };
+
+    let mut conflicts = Vec::new();
+    let result = repo.revparse_single(branch_ref_name).and_then(|target| {
+        let mut builder = git2::build::CheckoutBuilder::default();
+        builder
+            .safe()
+            .notify_on(git2::CheckoutNotificationType::CONFLICT)
+            .notify(|_, path, _, _, _| {
+                if let Some(path) = path {
+                    conflicts.push(path.display().to_string());
+                }
+                true
+            });
+
+        repo.checkout_tree(&target, Some(&mut builder))
+    });
+    if let Err(e) = result {
+        // HEAD did not move, so the stash applies back cleanly
+        if stashed {
+            repo.stash_pop(0, None).ok();
+        }
+        return Err(if conflicts.is_empty() {
+            Report::new(BranchError::BranchCreationError(e))
+        } else {
+            dirty_worktree_error(&conflicts)
+                .attach_printable("Use --dirty stash to move them to the new branch")
+        });
+    }
 
     repo.set_head(branch_ref_name)
         .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?;
-    repo.checkout_head(Some(git2::build::CheckoutBuilder::default().safe()))
+    if stashed {
+        repo.stash_pop(0, None).map_err(|e| {
+            Report::new(BranchError::BranchCreationError(e))
+                .attach_printable("Your changes are kept in stash@{0}")
+        })?;
+    }
+
+    Ok(())
+}
+
+// Checkout an existing local branch
+pub fn branch_checkout(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+    dirty_policy: DirtyPolicy,
+) -> Result<(), BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let mut repo = git_repository_open_from_workdir(path)?;
+    let branch_ref_name = repo
+        .find_branch(branch_name, BranchType::Local)
         .map_err(|e| Report::new(BranchError::BranchCreationError(e)))
+        .and_then(branch_ref_name)?;
+
+    checkout(&mut repo, &branch_ref_name, dirty_policy)
+}
+
+// Local branches that already belong to the issue: the branch itself, the
+// branches linked to the issue by gbc and the ones `is_issue_branch` accepts
+// (feature/ENG-12-old-title for feature/ENG-12-new-title). The exact match
+// comes first.
+pub fn issue_branches(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+    issue_id: &str,
+    is_issue_branch: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let config = repo
+        .config()
+        .map_err(|e| Report::new(BranchError::ConfigError(e)))?;
+    let is_linked = |name: &str| {
+        config
+            .get_string(&format!("branch.{}.gbc-issue", name))
+            .is_ok_and(|id| id == issue_id)
+    };
+
+    let mut branches: Vec<String> = repo
+        .branches(Some(BranchType::Local))
+        .map_err(|e| Report::new(BranchError::BranchCreationError(e)))?
+        .filter_map(|branch| branch.ok()?.0.name().ok()?.map(String::from))
+        .filter(|name| name == branch_name || is_linked(name) || is_issue_branch(name))
+        .collect();
+    branches.sort_by_key(|name| name != branch_name);
+
+    Ok(branches)
+}
+
+// Branch name itself when it is free, or its first free numbered variant,
+// feature/1-title-2
+pub fn branch_free_name(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+) -> Result<String, BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+
+    std::iter::once(branch_name.to_string())
+        .chain((2..).map(|suffix| format!("{}-{}", branch_name, suffix)))
+        .find(|name| repo.find_branch(name, BranchType::Local).is_err())
+        .ok_or_else(|| Report::new(BranchError::BranchAlreadyExists))
+}
+
+// Name given to the worktrees created by gbc, feature/1-title gives
+// gbc-feature-1-title
+fn worktree_name(branch_name: &str) -> String {
+    format!("{}{}", WORKTREE_PREFIX, branch_name.replace('/', "-"))
+}
+
+// Add a linked worktree for an existing branch, the path is relative to the
+// repository working directory. Returns the worktree path.
+pub fn worktree_add(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+    worktree_path: &Path,
+) -> Result<PathBuf, BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let worktree_error = |e| Report::new(BranchError::WorktreeError(e));
+    let worktree_path = repo
+        .workdir()
+        .map(|workdir| workdir.join(worktree_path))
+        .ok_or_else(|| {
+            worktree_error(git2::Error::from_str("Bare repositories have no workdir"))
+        })?;
+    let reference = repo
+        .find_branch(branch_name, BranchType::Local)
+        .map_err(worktree_error)?
+        .into_reference();
+    // libgit2 only creates the last directory, unlike `git worktree add`
+    if let Some(parent) = worktree_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| worktree_error(git2::Error::from_str(&e.to_string())))
+            .attach_printable_lazy(|| format!("Could not create {}", parent.display()))?;
+    }
+
+    repo.worktree(
+        &worktree_name(branch_name),
+        &worktree_path,
+        Some(git2::WorktreeAddOptions::new().reference(Some(&reference))),
+    )
+    .map_err(worktree_error)
+    .map(|worktree| worktree.path().to_path_buf())
+}
+
+// Create a branch and a linked worktree for it, HEAD of the current checkout
+// does not move. Returns the worktree path.
+pub fn worktree_create(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+    start_point: Option<&str>,
+    worktree_path: &Path,
+) -> Result<PathBuf, BranchError> {
+    let path = repo_path.clone().unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let branch_ref_name = create(&repo, branch_name, start_point)?;
+
+    worktree_add(repo_path, branch_name, worktree_path).inspect_err(|_| {
+        if let Ok(mut reference) = repo.find_reference(&branch_ref_name) {
+            reference.delete().ok();
+        }
+    })
+}
+
+// Worktree created by gbc
+pub struct WorktreeInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub branch: Option<String>,
+    // The worktree directory does not exist anymore
+    pub stale: bool,
+}
+
+// List the worktrees created by gbc
+pub fn worktree_list(repo_path: Option<PathBuf>) -> Result<Vec<WorktreeInfo>, BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let names = repo
+        .worktrees()
+        .map_err(|e| Report::new(BranchError::WorktreeError(e)))?;
+
+    Ok(names
+        .iter()
+        .flatten()
+        .filter(|name| name.starts_with(WORKTREE_PREFIX))
+        .filter_map(|name| repo.find_worktree(name).ok())
+        .map(|worktree| {
+            let branch = Repository::open_from_worktree(&worktree)
+                .ok()
+                .and_then(|repo| repo.head().ok()?.shorthand().map(String::from));
+
+            WorktreeInfo {
+                name: worktree.name().unwrap_or_default().to_string(),
+                path: worktree.path().to_path_buf(),
+                branch,
+                stale: worktree.validate().is_err(),
+            }
+        })
+        .collect())
+}
+
+// Prune the gbc worktrees whose directory was removed, or all of them with
+// their directory when forced. Returns the pruned worktree names.
+pub fn worktree_prune(repo_path: Option<PathBuf>, force: bool) -> Result<Vec<String>, BranchError> {
+    let path = repo_path.clone().unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let mut pruned = Vec::new();
+
+    for info in worktree_list(repo_path)? {
+        if !info.stale && !force {
+            continue;
+        }
+
+        let mut options = git2::WorktreePruneOptions::new();
+        options.valid(force).working_tree(force);
+        repo.find_worktree(&info.name)
+            .and_then(|worktree| worktree.prune(Some(&mut options)))
+            .map_err(|e| Report::new(BranchError::WorktreeError(e)))?;
+        pruned.push(info.name);
+    }
+
+    Ok(pruned)
+}
+
+// Credentials for the remotes, from the SSH agent or the git credential helper
+fn remote_callbacks<'a>(config: git2::Config) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut tried = CredentialType::empty();
+
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) && !tried.contains(CredentialType::SSH_KEY) {
+            tried.insert(CredentialType::SSH_KEY);
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && !tried.contains(CredentialType::USER_PASS_PLAINTEXT)
+        {
+            tried.insert(CredentialType::USER_PASS_PLAINTEXT);
+            return Cred::credential_helper(&config, url, username);
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !tried.contains(CredentialType::DEFAULT) {
+            tried.insert(CredentialType::DEFAULT);
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str("No credentials found for the remote"))
+    });
+
+    callbacks
+}
+
+// Fetch the base branch (HEAD branch when missing) from the remote and
+// fast-forward its local branch. Returns the up-to-date remote-tracking branch
+// (origin/main) to cut the new branch from.
+pub fn sync_base(
+    repo_path: Option<PathBuf>,
+    remote_name: &str,
+    base: Option<&str>,
+) -> Result<String, BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let remote_error = |e| Report::new(BranchError::RemoteError(e));
+
+    let branch_name = match base {
+        Some(base) => base
+            .strip_prefix(&format!("{}/", remote_name))
+            .unwrap_or(base)
+            .to_string(),
+        None => head_branch(&repo)?,
+    };
+    let tracking_name = format!("{}/{}", remote_name, branch_name);
+
+    let mut remote = repo.find_remote(remote_name).map_err(remote_error)?;
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(repo.config().map_err(remote_error)?));
+    remote
+        .fetch(
+            &[format!(
+                "+refs/heads/{}:refs/remotes/{}",
+                branch_name, tracking_name
+            )],
+            Some(&mut fetch_options),
+            None,
+        )
+        .map_err(remote_error)?;
+
+    let remote_commit = start_point_commit(&repo, &tracking_name)?;
+    if let Ok(mut local) = repo.find_branch(&branch_name, BranchType::Local) {
+        let remote_oid = remote_commit.id();
+        let fast_forward = match local.get().target() {
+            Some(local_oid) if local_oid != remote_oid => repo
+                .graph_descendant_of(remote_oid, local_oid)
+                .map_err(remote_error)?,
+            _ => false,
+        };
+
+        if fast_forward {
+            // The checked out branch needs its files updated as well
+            if local.is_head() {
+                repo.checkout_tree(
+                    remote_commit.as_object(),
+                    Some(git2::build::CheckoutBuilder::default().safe()),
+                )
+                .map_err(remote_error)?;
+            }
+            local
+                .get_mut()
+                .set_target(remote_oid, "gbc: fast-forward")
+                .map_err(remote_error)?;
+        }
+    }
+
+    Ok(tracking_name)
+}
+
+// Push a local branch to the remote and make it its upstream. Returns the
+// remote-tracking branch (origin/feature/42-title).
+pub fn branch_push(
+    repo_path: Option<PathBuf>,
+    remote_name: &str,
+    branch_name: &str,
+) -> Result<String, BranchError> {
+    let path = repo_path.clone().unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let remote_error = |e| Report::new(BranchError::RemoteError(e));
+
+    let mut remote = repo.find_remote(remote_name).map_err(remote_error)?;
+    let mut callbacks = remote_callbacks(repo.config().map_err(remote_error)?);
+    // Rejections from the remote (hooks, protected branches...) are only
+    // reported through this callback
+    callbacks.push_update_reference(|refname, status| match status {
+        Some(message) => Err(git2::Error::from_str(&format!(
+            "{} rejected by the remote: {}",
+            refname, message
+        ))),
+        None => Ok(()),
+    });
+    let mut push_options = git2::PushOptions::new();
+    push_options.remote_callbacks(callbacks);
+
+    let refname = format!("refs/heads/{}", branch_name);
+    remote
+        .push(
+            &[format!("{}:{}", refname, refname)],
+            Some(&mut push_options),
+        )
+        .map_err(remote_error)
+        .attach_printable_lazy(|| format!("Could not push {} to {}", branch_name, remote_name))?;
+
+    let tracking_name = format!("{}/{}", remote_name, branch_name);
+    branch_set_upstream(repo_path, branch_name, &tracking_name)?;
+
+    Ok(tracking_name)
+}
+
+// Set the upstream of a branch, origin/main
+pub fn branch_set_upstream(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+    upstream: &str,
+) -> Result<(), BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+
+    repo.find_branch(branch_name, BranchType::Local)
+        .and_then(|mut branch| branch.set_upstream(Some(upstream)))
+        .map_err(|e| Report::new(BranchError::RemoteError(e)))
+}
+
+// Record the issue of a branch in the repository git config
+pub fn branch_link_issue(
+    repo_path: Option<PathBuf>,
+    branch_name: &str,
+    link: &IssueLink,
+) -> Result<(), BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let config_error = |e| Report::new(BranchError::ConfigError(e));
+    let mut config = repo
+        .config()
+        .and_then(|config| config.open_level(git2::ConfigLevel::Local))
+        .map_err(config_error)?;
+
+    [
+        ("issue", &link.id),
+        ("url", &link.url),
+        ("driver", &link.driver),
+    ]
+    .into_iter()
+    .try_for_each(|(key, value)| {
+        config.set_str(&format!("branch.{}.gbc-{}", branch_name, key), value)
+    })
+    .map_err(config_error)
+}
+
+// Issue linked to a branch, the checked out one when missing. Returns the
+// branch name with its issue.
+pub fn branch_issue_link(
+    repo_path: Option<PathBuf>,
+    branch_name: Option<&str>,
+) -> Result<(String, IssueLink), BranchError> {
+    let path = repo_path.unwrap_or_else(|| PathBuf::from("."));
+    let repo = git_repository_open_from_workdir(path)?;
+    let branch_name = match branch_name {
+        Some(branch_name) => branch_name.to_string(),
+        None => head_branch(&repo)?,
+    };
+    let config = repo
+        .config()
+        .map_err(|e| Report::new(BranchError::ConfigError(e)))?;
+    let value = |key: &str| {
+        config
+            .get_string(&format!("branch.{}.gbc-{}", branch_name, key))
+            .ok()
+    };
+
+    let id = value("issue")
+        .ok_or_else(|| Report::new(BranchError::IssueNotLinked(branch_name.clone())))?;
+    let link = IssueLink {
+        id,
+        url: value("url").unwrap_or_default(),
+        driver: value("driver").unwrap_or_default(),
+    };
+
+    Ok((branch_name, link))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // Repository with a first commit on main, pushed to its origin remote, a
+    // bare repository reached through a file:// URL
+    struct Fixture {
+        dir: TempDir,
+        work: PathBuf,
+        remote: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Fixture {
+            let dir = TempDir::new().unwrap();
+            let remote = dir.path().join("remote.git");
+            Repository::init_bare(&remote)
+                .and_then(|bare| bare.set_head("refs/heads/main"))
+                .unwrap();
+
+            let work = dir.path().join("work");
+            let repo = Repository::init_opts(
+                &work,
+                git2::RepositoryInitOptions::new().initial_head("main"),
+            )
+            .unwrap();
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "gbc").unwrap();
+            config.set_str("user.email", "gbc@localhost").unwrap();
+            commit_file(&repo, "a.txt", "a\n");
+            repo.remote("origin", &format!("file://{}", remote.display()))
+                .unwrap();
+            push(&repo, "main");
+
+            Fixture { dir, work, remote }
+        }
+
+        fn repo(&self) -> Repository {
+            Repository::open(&self.work).unwrap()
+        }
+
+        fn path(&self) -> Option<PathBuf> {
+            Some(self.work.clone())
+        }
+
+        // Another clone of the remote, to push commits the fixture does not
+        // have yet
+        fn clone(&self, name: &str) -> Repository {
+            let url = format!("file://{}", self.remote.display());
+            Repository::clone(&url, self.dir.path().join(name)).unwrap()
+        }
+
+        fn read(&self, file: &str) -> Option<String> {
+            std::fs::read_to_string(self.work.join(file)).ok()
+        }
+    }
+
+    fn commit_file(repo: &Repository, file: &str, content: &str) -> git2::Oid {
+        std::fs::write(repo.workdir().unwrap().join(file), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().and_then(|head| head.peel_to_commit()).ok();
+        let signature = git2::Signature::now("gbc", "gbc@localhost").unwrap();
+
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            file,
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    fn push(repo: &Repository, branch: &str) {
+        repo.find_remote("origin")
+            .unwrap()
+            .push(&[format!("refs/heads/{0}:refs/heads/{0}", branch)], None)
+            .unwrap();
+    }
+
+    fn branch_target(repo: &Repository, name: &str, branch_type: BranchType) -> git2::Oid {
+        repo.find_branch(name, branch_type)
+            .unwrap()
+            .get()
+            .target()
+            .unwrap()
+    }
+
+    #[test]
+    fn sync_base_fetches_and_fast_forwards_the_checked_out_base() {
+        let fixture = Fixture::new();
+        let other = fixture.clone("other");
+        let pushed = commit_file(&other, "b.txt", "b\n");
+        push(&other, "main");
+
+        let tracking = sync_base(fixture.path(), "origin", Some("origin/main")).unwrap();
+
+        let repo = fixture.repo();
+        assert_eq!(tracking, "origin/main");
+        assert_eq!(
+            branch_target(&repo, "origin/main", BranchType::Remote),
+            pushed
+        );
+        assert_eq!(branch_target(&repo, "main", BranchType::Local), pushed);
+        assert_eq!(fixture.read("b.txt").as_deref(), Some("b\n"));
+    }
+
+    #[test]
+    fn sync_base_fast_forwards_a_base_that_is_not_checked_out() {
+        let fixture = Fixture::new();
+        let other = fixture.clone("other");
+        let pushed = commit_file(&other, "b.txt", "b\n");
+        push(&other, "main");
+        branch_create(
+            fixture.path(),
+            String::from("topic"),
+            None,
+            DirtyPolicy::Carry,
+        )
+        .unwrap();
+
+        let tracking = sync_base(fixture.path(), "origin", Some("main")).unwrap();
+
+        let repo = fixture.repo();
+        assert_eq!(tracking, "origin/main");
+        assert_eq!(branch_target(&repo, "main", BranchType::Local), pushed);
+        assert_eq!(head_branch(&repo).unwrap(), "topic");
+        assert_eq!(fixture.read("b.txt"), None);
+    }
+
+    #[test]
+    fn sync_base_leaves_a_diverged_local_base() {
+        let fixture = Fixture::new();
+        let other = fixture.clone("other");
+        let pushed = commit_file(&other, "b.txt", "b\n");
+        push(&other, "main");
+        let local = commit_file(&fixture.repo(), "c.txt", "c\n");
+
+        let tracking = sync_base(fixture.path(), "origin", None).unwrap();
+
+        let repo = fixture.repo();
+        assert_eq!(tracking, "origin/main");
+        assert_eq!(
+            branch_target(&repo, "origin/main", BranchType::Remote),
+            pushed
+        );
+        assert_eq!(branch_target(&repo, "main", BranchType::Local), local);
+    }
+
+    #[test]
+    fn branch_from_the_synced_base_tracks_it() {
+        let fixture = Fixture::new();
+        let other = fixture.clone("other");
+        let pushed = commit_file(&other, "b.txt", "b\n");
+        push(&other, "main");
+
+        let tracking = sync_base(fixture.path(), "origin", Some("main")).unwrap();
+        branch_create(
+            fixture.path(),
+            String::from("feature/1-title"),
+            Some(&tracking),
+            DirtyPolicy::Carry,
+        )
+        .unwrap();
+        branch_set_upstream(fixture.path(), "feature/1-title", &tracking).unwrap();
+
+        let repo = fixture.repo();
+        let branch = repo
+            .find_branch("feature/1-title", BranchType::Local)
+            .unwrap();
+        assert_eq!(head_branch(&repo).unwrap(), "feature/1-title");
+        assert_eq!(branch.get().target(), Some(pushed));
+        assert_eq!(
+            branch.upstream().unwrap().name().unwrap(),
+            Some("origin/main")
+        );
+    }
+
+    #[test]
+    fn sync_base_fails_without_the_remote() {
+        let fixture = Fixture::new();
+
+        let error = sync_base(fixture.path(), "upstream", None).unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            BranchError::RemoteError(_)
+        ));
+    }
+
+    // main gets a second commit touching `file`, a.txt is then left modified
+    // in the worktree; returns the first commit to branch from
+    fn dirty_fixture(file: &str) -> (Fixture, String) {
+        let fixture = Fixture::new();
+        let repo = fixture.repo();
+        let first = branch_target(&repo, "main", BranchType::Local).to_string();
+        commit_file(&repo, file, "second\n");
+        std::fs::write(fixture.work.join("a.txt"), "changed\n").unwrap();
+        (fixture, first)
+    }
+
+    fn stash_count(fixture: &Fixture) -> usize {
+        let mut count = 0;
+        fixture
+            .repo()
+            .stash_foreach(|_, _, _| {
+                count += 1;
+                true
+            })
+            .unwrap();
+        count
+    }
+
+    #[test]
+    fn refuse_lists_the_dirty_files_and_removes_the_new_branch() {
+        let (fixture, first) = dirty_fixture("b.txt");
+
+        let error = branch_create(
+            fixture.path(),
+            String::from("feature/1"),
+            Some(&first),
+            DirtyPolicy::Refuse,
+        )
+        .unwrap_err();
+
+        let repo = fixture.repo();
+        assert!(matches!(
+            error.current_context(),
+            BranchError::DirtyWorktree
+        ));
+        assert!(format!("{:?}", error).contains("a.txt"));
+        assert!(repo.find_branch("feature/1", BranchType::Local).is_err());
+        assert_eq!(head_branch(&repo).unwrap(), "main");
+        assert_eq!(fixture.read("a.txt").as_deref(), Some("changed\n"));
+    }
+
+    #[test]
+    fn stash_reapplies_the_changes_on_the_new_branch() {
+        let (fixture, first) = dirty_fixture("b.txt");
+
+        branch_create(
+            fixture.path(),
+            String::from("feature/1"),
+            Some(&first),
+            DirtyPolicy::Stash,
+        )
+        .unwrap();
+
+        let repo = fixture.repo();
+        assert_eq!(head_branch(&repo).unwrap(), "feature/1");
+        assert_eq!(fixture.read("a.txt").as_deref(), Some("changed\n"));
+        assert_eq!(fixture.read("b.txt"), None);
+        assert_eq!(stash_count(&fixture), 0);
+    }
+
+    #[test]
+    fn carry_keeps_changes_that_do_not_conflict() {
+        let (fixture, first) = dirty_fixture("b.txt");
+
+        branch_create(
+            fixture.path(),
+            String::from("feature/1"),
+            Some(&first),
+            DirtyPolicy::Carry,
+        )
+        .unwrap();
+
+        let repo = fixture.repo();
+        assert_eq!(head_branch(&repo).unwrap(), "feature/1");
+        assert_eq!(fixture.read("a.txt").as_deref(), Some("changed\n"));
+        assert_eq!(fixture.read("b.txt"), None);
+    }
+
+    #[test]
+    fn carry_refuses_conflicting_changes_and_removes_the_new_branch() {
+        let (fixture, first) = dirty_fixture("a.txt");
+
+        let error = branch_create(
+            fixture.path(),
+            String::from("feature/1"),
+            Some(&first),
+            DirtyPolicy::Carry,
+        )
+        .unwrap_err();
+
+        let repo = fixture.repo();
+        assert!(matches!(
+            error.current_context(),
+            BranchError::DirtyWorktree
+        ));
+        assert!(format!("{:?}", error).contains("a.txt"));
+        assert!(repo.find_branch("feature/1", BranchType::Local).is_err());
+        assert_eq!(head_branch(&repo).unwrap(), "main");
+        assert_eq!(fixture.read("a.txt").as_deref(), Some("changed\n"));
+    }
+
+    #[test]
+    fn clean_worktree_switches_under_any_policy() {
+        let fixture = Fixture::new();
+
+        branch_create(
+            fixture.path(),
+            String::from("feature/1"),
+            None,
+            DirtyPolicy::Refuse,
+        )
+        .unwrap();
+
+        assert_eq!(head_branch(&fixture.repo()).unwrap(), "feature/1");
+        assert_eq!(stash_count(&fixture), 0);
+    }
+
+    #[test]
+    fn branch_push_creates_the_remote_branch_and_tracks_it() {
+        let fixture = Fixture::new();
+        branch_create(
+            fixture.path(),
+            String::from("feature/1-title"),
+            None,
+            DirtyPolicy::Refuse,
+        )
+        .unwrap();
+        let pushed = commit_file(&fixture.repo(), "b.txt", "b\n");
+
+        let tracking = branch_push(fixture.path(), "origin", "feature/1-title").unwrap();
+
+        let remote = Repository::open_bare(&fixture.remote).unwrap();
+        let repo = fixture.repo();
+        let branch = repo
+            .find_branch("feature/1-title", BranchType::Local)
+            .unwrap();
+        assert_eq!(tracking, "origin/feature/1-title");
+        assert_eq!(
+            remote.refname_to_id("refs/heads/feature/1-title").unwrap(),
+            pushed
+        );
+        assert_eq!(
+            branch.upstream().unwrap().name().unwrap(),
+            Some("origin/feature/1-title")
+        );
+    }
+
+    #[test]
+    fn branch_push_rejected_leaves_no_upstream() {
+        let fixture = Fixture::new();
+        let other = fixture.clone("other");
+        other
+            .branch(
+                "feature/1-title",
+                &other.head().unwrap().peel_to_commit().unwrap(),
+                false,
+            )
+            .unwrap();
+        other.set_head("refs/heads/feature/1-title").unwrap();
+        commit_file(&other, "b.txt", "theirs\n");
+        push(&other, "feature/1-title");
+        branch_create(
+            fixture.path(),
+            String::from("feature/1-title"),
+            None,
+            DirtyPolicy::Refuse,
+        )
+        .unwrap();
+        commit_file(&fixture.repo(), "b.txt", "ours\n");
+
+        let error = branch_push(fixture.path(), "origin", "feature/1-title").unwrap_err();
+
+        let repo = fixture.repo();
+        let branch = repo
+            .find_branch("feature/1-title", BranchType::Local)
+            .unwrap();
+        assert!(matches!(
+            error.current_context(),
+            BranchError::RemoteError(_)
+        ));
+        assert!(branch.upstream().is_err());
+    }
+
+    #[test]
+    fn issue_branches_finds_linked_and_matching_branches() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        for name in [
+            "feature/1-new",
+            "feature/1-old",
+            "renamed",
+            "feature/12-other",
+        ] {
+            repo.branch(name, &head, false).unwrap();
+        }
+        let link = IssueLink {
+            id: String::from("1"),
+            url: String::from("https://api.github.com"),
+            driver: String::from("github"),
+        };
+        branch_link_issue(fixture.path(), "renamed", &link).unwrap();
+
+        let branches = issue_branches(fixture.path(), "feature/1-new", "1", |name| {
+            name.starts_with("feature/1-")
+        })
+        .unwrap();
+
+        assert_eq!(branches[0], "feature/1-new");
+        let mut others = branches[1..].to_vec();
+        others.sort();
+        assert_eq!(others, ["feature/1-old", "renamed"]);
+    }
+
+    #[test]
+    fn worktree_create_keeps_head_and_resolves_the_path_from_the_workdir() {
+        let fixture = Fixture::new();
+
+        let path = worktree_create(
+            fixture.path(),
+            "feature/1-title",
+            None,
+            Path::new("../worktrees/1-title"),
+        )
+        .unwrap();
+
+        let expected = fixture.dir.path().join("worktrees/1-title");
+        assert_eq!(
+            path.canonicalize().unwrap(),
+            expected.canonicalize().unwrap()
+        );
+        assert!(expected.join("a.txt").exists());
+        assert_eq!(fixture.repo().head().unwrap().shorthand(), Some("main"));
+        let worktree = Repository::open(&expected).unwrap();
+        assert_eq!(
+            worktree.head().unwrap().shorthand(),
+            Some("feature/1-title")
+        );
+    }
+
+    #[test]
+    fn worktree_create_removes_the_branch_when_the_worktree_fails() {
+        let fixture = Fixture::new();
+        let occupied = fixture.dir.path().join("occupied");
+        std::fs::create_dir(&occupied).unwrap();
+        std::fs::write(occupied.join("file.txt"), "taken\n").unwrap();
+
+        let error =
+            worktree_create(fixture.path(), "feature/1-title", None, &occupied).unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            BranchError::WorktreeError(_)
+        ));
+        assert!(fixture
+            .repo()
+            .find_branch("feature/1-title", BranchType::Local)
+            .is_err());
+    }
+
+    #[test]
+    fn worktree_list_marks_the_removed_directories_stale() {
+        let fixture = Fixture::new();
+        let path =
+            worktree_create(fixture.path(), "feature/1-title", None, Path::new("../wt")).unwrap();
+
+        let listed = worktree_list(fixture.path()).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "gbc-feature-1-title");
+        assert_eq!(listed[0].branch.as_deref(), Some("feature/1-title"));
+        assert!(!listed[0].stale);
+
+        std::fs::remove_dir_all(path).unwrap();
+
+        assert!(worktree_list(fixture.path()).unwrap()[0].stale);
+    }
+
+    #[test]
+    fn worktree_prune_only_removes_stale_worktrees_unless_forced() {
+        let fixture = Fixture::new();
+        let stale =
+            worktree_create(fixture.path(), "feature/1-old", None, Path::new("../old")).unwrap();
+        let live =
+            worktree_create(fixture.path(), "feature/2-new", None, Path::new("../new")).unwrap();
+        std::fs::remove_dir_all(stale).unwrap();
+
+        let pruned = worktree_prune(fixture.path(), false).unwrap();
+
+        assert_eq!(pruned, ["gbc-feature-1-old"]);
+        let listed = worktree_list(fixture.path()).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "gbc-feature-2-new");
+        assert!(live.exists());
+
+        let pruned = worktree_prune(fixture.path(), true).unwrap();
+
+        assert_eq!(pruned, ["gbc-feature-2-new"]);
+        assert!(worktree_list(fixture.path()).unwrap().is_empty());
+        assert!(!live.exists());
+        // The branches are kept, only the checkouts are removed
+        assert!(fixture
+            .repo()
+            .find_branch("feature/2-new", BranchType::Local)
+            .is_ok());
+    }
 }
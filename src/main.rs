@@ -4,17 +4,26 @@
 mod drivers;
 mod features;
 mod git;
+mod secrets;
+mod slug;
+mod template;
 mod ui;
 
-use clap::{Parser, Subcommand};
-use error_stack::Result;
-use features::global_config::Config;
-use features::init::ProjectConfig;
+use clap::{Args, Parser, Subcommand};
+use drivers::{issue::Issue, refreshing::RefreshingDriver, Driver, DriverError, IssueDriver};
+use error_stack::{Report, Result, ResultExt};
+use features::global_config::{Access, Config, ConfigError, Credentials};
+use features::init::{OnStart, ProjectConfig};
+use features::oauth::{self, LoginOptions};
+use features::pull_request::{self, PullRequest, PullRequestConfig, PullRequestError};
+use git::{BranchError, DirtyPolicy, ExistingBranchAction, IssueLink};
+use secrets::SecretStore;
 use std::{error::Error, fmt::Display};
 use std::{
     path::PathBuf,
     process::{ExitCode, Termination},
 };
+use template::{Context, Template};
 use ui::fuzzy_finder;
 
 // Command interface
@@ -30,8 +39,34 @@ enum GlobalConfigCommands {
     /// Create or update the global configuration file with the credentials
     /// provided
     AddCredentials,
-    /// Display the global configuration file pretty printed
-    Display,
+    /// Display the global configuration file pretty printed, with the secrets
+    /// masked
+    Display {
+        /// Print the passwords and tokens, read from their store
+        #[arg(long)]
+        show_secrets: bool,
+    },
+    /// List the URLs of the credentials with their access type and store
+    List,
+    /// Remove the credentials of a URL, and their secret from its store
+    Remove { url: String },
+    /// Check the credentials of a URL by asking the issue tracker who they
+    /// authenticate
+    Test {
+        url: String,
+        /// Driver of the issue tracker, the one of the project configuration
+        /// when it uses the same URL
+        #[arg(long)]
+        driver: Option<String>,
+    },
+    /// Change the URL or the access of credentials
+    Edit { url: String },
+    /// Move the secrets of every credentials to another store, the new
+    /// credentials are then saved there as well
+    MigrateSecrets {
+        #[arg(value_enum)]
+        store: SecretStore,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -43,14 +78,93 @@ enum Commands {
     },
     /// Initialize a gbc project
     Init {},
+    /// Log in with OAuth in a browser and save the tokens as credentials
+    Login {
+        #[arg(value_enum)]
+        provider: oauth::Provider,
+        /// Client id of the OAuth application
+        #[arg(long)]
+        client_id: String,
+        /// Client secret of the Jira OAuth application, asked when missing
+        #[arg(long)]
+        client_secret: Option<String>,
+        /// Authorization server URL, the one of the provider by default
+        #[arg(long)]
+        auth_url: Option<String>,
+        /// API URL the credentials are saved for, the url of the project
+        /// configuration
+        #[arg(long)]
+        url: Option<String>,
+        /// Scopes requested, space separated
+        #[arg(long)]
+        scope: Option<String>,
+        /// Port of the Jira callback URL, http://localhost:8765/callback by
+        /// default
+        #[arg(long)]
+        redirect_port: Option<u16>,
+    },
+    /// List the issues of the board
+    List {
+        /// Print the issues as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Display a fuzzy_finder interface to select an issue from
     /// the board and create a git branch from it
-    New {
-        /// You can select the type of branch you want to create, a default list
-        /// will be created by default in your project configuration file.
-        type_of_branch: Option<String>,
-        #[arg(required = false)]
-        path: Option<PathBuf>,
+    New(NewOptions),
+    /// Show the issue linked to a branch, the current one by default
+    Info { branch: Option<String> },
+    /// Manage the worktrees created by gbc
+    Worktree {
+        #[command(subcommand)]
+        command: WorktreeCommands,
+    },
+}
+
+#[derive(Args, Debug)]
+struct NewOptions {
+    /// You can select the type of branch you want to create, a default list
+    /// will be created by default in your project configuration file.
+    type_of_branch: Option<String>,
+    #[arg(required = false)]
+    path: Option<PathBuf>,
+    /// Branch or ref to create the branch from (main, origin/develop...),
+    /// by default the base of the branch kind or HEAD
+    #[arg(long)]
+    from: Option<String>,
+    /// Fetch and fast-forward the base branch from the remote first, the
+    /// new branch then tracks the remote base branch
+    #[arg(long)]
+    sync: bool,
+    /// What to do when the issue already has a branch, asked when missing
+    #[arg(long, value_enum)]
+    on_existing: Option<ExistingBranchAction>,
+    /// What to do with uncommitted changes, by default the dirty_policy
+    /// of the project configuration
+    #[arg(long, value_enum)]
+    dirty: Option<DirtyPolicy>,
+    /// Create the branch in a new linked worktree instead of switching
+    /// branches, in the worktree_path of the project configuration
+    #[arg(long)]
+    worktree: bool,
+    /// Push the new branch to the remote and set it as its upstream
+    #[arg(long)]
+    push: bool,
+    /// Push the new branch and open a draft pull request for it, configured
+    /// in the pull_request section of the project configuration
+    #[arg(long)]
+    pr: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum WorktreeCommands {
+    /// List the worktrees created by gbc
+    List,
+    /// Prune the worktrees created by gbc whose directory was removed
+    Prune {
+        /// Also remove the worktrees that still exist, with their directory
+        #[arg(long)]
+        force: bool,
     },
 }
 
@@ -75,6 +189,126 @@ fn update_err_ctx<T, E>(result: Result<T, E>) -> Result<T, CLIError> {
     })
 }
 
+// Load the project configuration and the issue tracker driver it selects
+fn load_driver(config: &Config) -> Result<(ProjectConfig, Box<dyn IssueDriver>), CLIError> {
+    let project_config = update_err_ctx(ProjectConfig::load())?;
+    let credentials = find_credentials(config, &project_config.url)?;
+    let driver = build_driver(
+        project_config.driver.clone(),
+        project_config.url.clone(),
+        project_config.identifier.clone(),
+        credentials,
+    )?;
+
+    Ok((project_config, driver))
+}
+
+// Build a driver, OAuth access tokens expire so they are refreshed when the
+// issue tracker rejects them
+fn build_driver(
+    kind: Driver,
+    url: String,
+    identifier: String,
+    credentials: Option<Credentials>,
+) -> Result<Box<dyn IssueDriver>, CLIError> {
+    let driver = update_err_ctx(drivers::build(
+        &kind,
+        &url,
+        &identifier,
+        credentials.as_ref(),
+    ))?;
+
+    Ok(match credentials {
+        Some(
+            credentials @ Credentials {
+                access: Access::OAuth { .. },
+                ..
+            },
+        ) => Box::new(RefreshingDriver::new(
+            driver,
+            Box::new(move || {
+                let refreshed = refresh_credentials(&credentials)?;
+                drivers::build(&kind, &url, &identifier, Some(&refreshed))
+            }),
+        )),
+        _ => driver,
+    })
+}
+
+// Refresh OAuth credentials and save them
+fn refresh_credentials(credentials: &Credentials) -> Result<Credentials, DriverError> {
+    let refreshed = oauth::refresh(credentials)
+        .change_context(DriverError::Unauthorized)
+        .attach_printable("Could not refresh the OAuth access token")?;
+    Config::load()
+        .and_then(|mut config| config.create_or_update_credentials(refreshed.clone()))
+        .change_context(DriverError::Unauthorized)?;
+
+    Ok(refreshed)
+}
+
+// Open the pull request, once more with a refreshed OAuth access token when
+// the forge rejects the saved one
+fn open_pull_request(
+    config: &PullRequestConfig,
+    credentials: Option<Credentials>,
+    pull_request: &PullRequest,
+) -> Result<String, PullRequestError> {
+    let result = pull_request::open(config, credentials.as_ref(), pull_request);
+
+    match (result, credentials) {
+        (
+            Err(mut e),
+            Some(
+                credentials @ Credentials {
+                    access: Access::OAuth { .. },
+                    ..
+                },
+            ),
+        ) if matches!(e.current_context(), PullRequestError::Unauthorized) => {
+            match refresh_credentials(&credentials) {
+                Ok(refreshed) => pull_request::open(config, Some(&refreshed), pull_request),
+                Err(refresh_error) => {
+                    e.extend_one(refresh_error.change_context(PullRequestError::Unauthorized));
+                    Err(e)
+                }
+            }
+        }
+        (result, _) => result,
+    }
+}
+
+// Ask the issue tracker who the credentials of a URL authenticate, its driver
+// is the one given or the one of the project configuration using the URL
+fn test_credentials(config: &Config, url: String, driver: Option<String>) -> Result<(), CLIError> {
+    let credentials = update_err_ctx(config.get_credentials(url.clone()))?;
+    let kind = match driver {
+        Some(name) => update_err_ctx(name.parse::<Driver>())?,
+        None => match ProjectConfig::load() {
+            Ok(project_config) if project_config.url == url => project_config.driver,
+            _ => {
+                return update_err_ctx(Err(Report::new(DriverError::DriverNotFound(url))
+                    .attach_printable("Give the driver of the issue tracker with --driver")))
+            }
+        },
+    };
+
+    let driver = build_driver(kind, url.clone(), String::new(), Some(credentials))?;
+    let identity = update_err_ctx(driver.whoami())?;
+    println!("The credentials of {} authenticate {}", url, identity);
+
+    Ok(())
+}
+
+// Credentials saved for the URL, the missing ones are reported by their users
+fn find_credentials(config: &Config, url: &str) -> Result<Option<Credentials>, CLIError> {
+    match config.get_credentials(url.to_string()) {
+        Ok(credentials) => Ok(Some(credentials)),
+        Err(e) if matches!(e.current_context(), ConfigError::CredentialsNotFound) => Ok(None),
+        Err(e) => update_err_ctx(Err(e)),
+    }
+}
+
 // Route the command to the right function
 fn router(opts: Opts) -> Result<(), CLIError> {
     let mut config = update_err_ctx(Config::load())?;
@@ -86,32 +320,319 @@ fn router(opts: Opts) -> Result<(), CLIError> {
 
                 config.create_or_update_credentials(credentials)
             }
-            GlobalConfigCommands::Display => config.print(),
+            GlobalConfigCommands::Display { show_secrets } => config.print(show_secrets),
+            GlobalConfigCommands::List => {
+                config.list();
+                Ok(())
+            }
+            GlobalConfigCommands::Remove { url } => config.remove_credentials(&url).map(|_| {
+                println!("Removed the credentials of {}", url);
+            }),
+            GlobalConfigCommands::Test { url, driver } => {
+                return test_credentials(&config, url, driver)
+            }
+            GlobalConfigCommands::Edit { url } => {
+                config.get_stored_credentials(&url).and_then(|current| {
+                    config.replace_credentials(&url, ui::edit_credentials(current))
+                })
+            }
+            GlobalConfigCommands::MigrateSecrets { store } => config.migrate_secrets(store),
         }),
         Commands::Init {} => {
             let project_config = ui::get_project_config();
             update_err_ctx(project_config.init()).map(|_| ())
         }
-        Commands::New {
-            path,
-            type_of_branch,
+        Commands::Login {
+            provider,
+            client_id,
+            client_secret,
+            auth_url,
+            url,
+            scope,
+            redirect_port,
         } => {
-            let project_config = update_err_ctx(ProjectConfig::load())?;
-            let credentials = update_err_ctx(config.get_credentials(project_config.url))?;
-            let issues = drivers::mock::get_issues(&credentials.url).unwrap_or_default();
-            let branch_formatter = project_config
-                .branch_kinds
-                .iter()
-                .find(|branch_kind| branch_kind.kind == type_of_branch.clone().unwrap_or_default())
-                .map(|branch_kind| branch_kind.formatter.clone())
-                .unwrap_or_else(|| String::from("feature/{id}-{name}"));
-            let issue = update_err_ctx(fuzzy_finder::render(issues))?;
-
-            let branch_name = branch_formatter
-                .replace("{id}", &issue.id)
-                .replace("{name}", &issue.title.replace(' ', "-"));
-
-            update_err_ctx(git::branch_create(path, branch_name).map(|_| Ok(())))?
+            let client_secret = match provider {
+                oauth::Provider::Jira => client_secret.or_else(|| Some(ui::get_client_secret())),
+                _ => client_secret,
+            };
+            let credentials = update_err_ctx(oauth::login(
+                provider,
+                LoginOptions {
+                    client_id,
+                    client_secret,
+                    auth_url,
+                    url,
+                    scope,
+                    redirect_port,
+                },
+            ))?;
+            let url = credentials.url.clone();
+            update_err_ctx(config.create_or_update_credentials(credentials))?;
+            println!("Logged in, credentials saved for {}", url);
+
+            Ok(())
+        }
+        Commands::List { json } => {
+            let (_, driver) = load_driver(&config)?;
+            let issues = update_err_ctx(driver.get_issues())?;
+
+            if json {
+                let output = serde_json::to_string_pretty(&issues).unwrap_or_default();
+                println!("{}", output);
+            } else {
+                issues
+                    .iter()
+                    .for_each(|issue| println!("{}", fuzzy_finder::label(issue)));
+            }
+
+            Ok(())
+        }
+        Commands::New(options) => new_branch(&config, options),
+        Commands::Info { branch } => issue_info(&config, branch),
+        Commands::Worktree { command } => match command {
+            WorktreeCommands::List => {
+                let worktrees = update_err_ctx(git::worktree_list(None))?;
+                worktrees.iter().for_each(|worktree| {
+                    println!(
+                        "{}\t{}\t{}{}",
+                        worktree.name,
+                        worktree.branch.as_deref().unwrap_or("-"),
+                        worktree.path.display(),
+                        if worktree.stale { " (stale)" } else { "" }
+                    )
+                });
+
+                Ok(())
+            }
+            WorktreeCommands::Prune { force } => {
+                let pruned = update_err_ctx(git::worktree_prune(None, force))?;
+                pruned
+                    .iter()
+                    .for_each(|name| println!("Pruned worktree {}", name));
+
+                Ok(())
+            }
+        },
+    }
+}
+
+// Select an issue and create its branch
+fn new_branch(config: &Config, options: NewOptions) -> Result<(), CLIError> {
+    let path = options.path;
+    let (project_config, driver) = load_driver(config)?;
+    let issues = update_err_ctx(driver.get_issues())?;
+    let mut issue = update_err_ctx(fuzzy_finder::render(issues))?;
+    if driver.capabilities().get_issue {
+        issue = update_err_ctx(driver.get_issue(&issue.id))?;
+    }
+
+    let dirty_policy = options.dirty.unwrap_or(project_config.dirty_policy);
+    let worktree = options.worktree || project_config.worktree;
+    let kind = options
+        .type_of_branch
+        .unwrap_or_else(|| String::from("feature"));
+    let template = update_err_ctx(Template::parse(project_config.formatter(&kind)))?;
+    let context = Context::new(&issue, project_config.slug_max_length)
+        .with("kind", Some(kind.clone()))
+        .with("user", git::user_name(path.clone()))
+        .with("repo", git::repository_name(path.clone()));
+    let mut branch_name = template.render(&context);
+    update_err_ctx(slug::validate_branch_name(&branch_name))?;
+
+    let pattern = template.pattern(&context);
+    let existing = update_err_ctx(git::issue_branches(
+        path.clone(),
+        &branch_name,
+        &issue.id,
+        |name| {
+            pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.matches(name))
+        },
+    ))?;
+    let mut existing_branch = None;
+    if !existing.is_empty() {
+        match options
+            .on_existing
+            .unwrap_or_else(|| ui::get_existing_branch_action(&existing))
+        {
+            ExistingBranchAction::Checkout => existing_branch = Some(existing[0].clone()),
+            ExistingBranchAction::Suffix => {
+                branch_name = update_err_ctx(git::branch_free_name(path.clone(), &branch_name))?;
+            }
+            ExistingBranchAction::Abort => {
+                return update_err_ctx(Err(Report::new(BranchError::BranchAlreadyExists)
+                    .attach_printable(format!("Existing branches: {}", existing.join(", ")))));
+            }
+        }
+    }
+
+    let worktree_path = if worktree {
+        let template = update_err_ctx(Template::parse(&project_config.worktree_path))?;
+        Some(PathBuf::from(template.render(&context)))
+    } else {
+        None
+    };
+
+    if let Some(existing_branch) = existing_branch {
+        return match worktree_path {
+            Some(worktree_path) => {
+                let created =
+                    update_err_ctx(git::worktree_add(path, &existing_branch, &worktree_path))?;
+                println!("Worktree created at {}", created.display());
+                Ok(())
+            }
+            None => update_err_ctx(git::branch_checkout(path, &existing_branch, dirty_policy)),
+        };
+    }
+
+    // Checked before creating the branch to fail early
+    let pull_request_config = match &project_config.pull_request {
+        Some(pull_request_config) if options.pr || pull_request_config.auto => {
+            Some(pull_request_config)
+        }
+        Some(_) => None,
+        None if options.pr => {
+            return update_err_ctx(Err(Report::new(PullRequestError::NotConfigured)
+                .attach_printable("Add a pull_request section to your .gbc file")));
+        }
+        None => None,
+    };
+
+    let base = options
+        .from
+        .or_else(|| project_config.base(&kind).map(String::from));
+    let mut start_point = base.clone();
+    let mut upstream = None;
+    if options.sync || project_config.sync {
+        let tracking_branch = update_err_ctx(git::sync_base(
+            path.clone(),
+            &project_config.remote,
+            start_point.as_deref(),
+        ))?;
+        start_point = Some(tracking_branch.clone());
+        upstream = Some(tracking_branch);
+    }
+
+    match worktree_path {
+        Some(worktree_path) => {
+            let created = update_err_ctx(git::worktree_create(
+                path.clone(),
+                &branch_name,
+                start_point.as_deref(),
+                &worktree_path,
+            ))?;
+            println!("Worktree created at {}", created.display());
+        }
+        None => update_err_ctx(git::branch_create(
+            path.clone(),
+            branch_name.clone(),
+            start_point.as_deref(),
+            dirty_policy,
+        ))?,
+    }
+    let link = IssueLink {
+        id: issue.id.clone(),
+        url: project_config.url.clone(),
+        driver: project_config.driver.name().to_string(),
+    };
+    update_err_ctx(git::branch_link_issue(path.clone(), &branch_name, &link))?;
+    start_issue(driver.as_ref(), &issue.id, &project_config.on_start);
+
+    if !(options.push || project_config.push || pull_request_config.is_some()) {
+        return match upstream {
+            Some(upstream) => {
+                update_err_ctx(git::branch_set_upstream(path, &branch_name, &upstream))
+            }
+            None => Ok(()),
+        };
+    }
+
+    let pushed = update_err_ctx(git::branch_push(path, &project_config.remote, &branch_name))?;
+    println!("Branch pushed to {}", pushed);
+
+    if let Some(pull_request_config) = pull_request_config {
+        let title = update_err_ctx(Template::parse(&pull_request_config.title))?;
+        let body = update_err_ctx(Template::parse(&pull_request_config.body))?;
+        // The base branch without its remote, origin/main targets main
+        let remote_prefix = format!("{}/", project_config.remote);
+        let target = pull_request_config.target.as_deref().or_else(|| {
+            base.as_deref()
+                .map(|base| base.strip_prefix(&remote_prefix).unwrap_or(base))
+        });
+
+        let pull_request = PullRequest {
+            source: &branch_name,
+            target,
+            title: title.render(&context),
+            body: body.render(&context),
+        };
+        let credentials = find_credentials(config, &pull_request_config.url())?;
+        let pull_request_url = update_err_ctx(open_pull_request(
+            pull_request_config,
+            credentials,
+            &pull_request,
+        ))?;
+        println!("Draft pull request opened at {}", pull_request_url);
+    }
+
+    Ok(())
+}
+
+// Show the issue linked to a branch, with its live details when the project
+// driver is the one the branch was created with
+fn issue_info(config: &Config, branch: Option<String>) -> Result<(), CLIError> {
+    let (branch, link) = update_err_ctx(git::branch_issue_link(None, branch.as_deref()))?;
+
+    let live_issue = match load_driver(config) {
+        Ok((project_config, driver))
+            if project_config.driver.name() == link.driver
+                && project_config.url == link.url
+                && driver.capabilities().get_issue =>
+        {
+            driver
+                .get_issue(&link.id)
+                .inspect_err(|e| eprintln!("Could not fetch issue {}\n{e:?}", link.id))
+                .ok()
+        }
+        _ => None,
+    };
+    let issue = live_issue.unwrap_or_else(|| Issue {
+        id: link.id.clone(),
+        ..Default::default()
+    });
+
+    println!("{:<12}{}", "branch", branch);
+    println!("{:<12}{} {}", "tracker", link.driver, link.url);
+    Issue::FIELDS
+        .iter()
+        .filter_map(|name| Some((name, issue.field(name).filter(|value| !value.is_empty())?)))
+        .for_each(|(name, value)| println!("{:<12}{}", name, value));
+
+    Ok(())
+}
+
+// Move and assign the issue as configured in on_start, the branch already
+// exists so the failures are only reported
+fn start_issue(driver: &dyn IssueDriver, id: &str, on_start: &OnStart) {
+    let capabilities = driver.capabilities();
+
+    if let Some(status) = &on_start.status {
+        if !capabilities.transition {
+            eprintln!("The driver cannot change the issue status, skipping");
+        } else if let Err(e) = driver.transition_issue(id, status) {
+            eprintln!("Could not move issue {} to {}\n{e:?}", id, status);
+        } else {
+            println!("Issue {} moved to {}", id, status);
+        }
+    }
+    if on_start.assign {
+        if !capabilities.assign {
+            eprintln!("The driver cannot assign the issue, skipping");
+        } else if let Err(e) = driver.assign_issue(id) {
+            eprintln!("Could not assign issue {}\n{e:?}", id);
+        } else {
+            println!("Issue {} assigned to you", id);
         }
     }
 }
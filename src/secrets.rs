@@ -0,0 +1,325 @@
+// Storage of the credential secrets, passwords and tokens, outside of the
+// configuration file. The credentials only keep a reference to their store and
+// the secret is found by the credentials URL.
+
+use age::secrecy::Secret;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::{error::Error, fmt::Display};
+
+const KEYRING_SERVICE: &str = "gbc";
+// Entry read to check that the keyring answers
+const KEYRING_PROBE: &str = "gbc-probe";
+const PASSPHRASE_VARIABLE: &str = "GBC_PASSPHRASE";
+
+// Errors that can occur when reading or writing a secret
+#[derive(Debug)]
+pub enum SecretError {
+    SecretNotFound(String),
+    KeyringError,
+    FileError,
+    EncryptionError,
+    DecryptionError,
+}
+
+impl Display for SecretError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::SecretNotFound(url) => write!(fmt, "No secret stored for {}", url),
+            SecretError::KeyringError => fmt.write_str("Error talking to the OS keyring"),
+            SecretError::FileError => fmt.write_str("Error reading the secrets file"),
+            SecretError::EncryptionError => fmt.write_str("Could not encrypt the secrets"),
+            SecretError::DecryptionError => {
+                fmt.write_str("Could not decrypt the secrets, wrong passphrase?")
+            }
+        }
+    }
+}
+
+impl Error for SecretError {}
+
+// Where the secret of some credentials is kept, the configurations and
+// credentials saying nothing predate the stores and are in plaintext
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SecretStore {
+    /// In the configuration file, readable by anyone with access to it
+    #[default]
+    Plaintext,
+    /// In the OS keyring, Secret Service, macOS Keychain or Windows Credential Manager
+    Keyring,
+    /// In a file encrypted with a passphrase, next to the configuration file
+    EncryptedFile,
+}
+
+impl SecretStore {
+    pub fn is_plaintext(&self) -> bool {
+        *self == SecretStore::Plaintext
+    }
+
+    // Store of the new configurations, the OS keyring or the encrypted file
+    // when there is none (no Secret Service on a server or in a container)
+    pub fn preferred() -> SecretStore {
+        if KeyringBackend::is_available() {
+            SecretStore::Keyring
+        } else {
+            SecretStore::EncryptedFile
+        }
+    }
+}
+
+// Common interface between the secret stores, the secrets are keyed by the
+// credentials URL
+pub trait SecretBackend {
+    fn get(&self, url: &str) -> Result<String, SecretError>;
+    fn set(&self, url: &str, secret: &str) -> Result<(), SecretError>;
+    fn delete(&self, url: &str) -> Result<(), SecretError>;
+}
+
+// Backend of a store, there is none for the plaintext store as its secrets
+// stay in the configuration file
+pub fn backend(store: SecretStore) -> Option<Box<dyn SecretBackend>> {
+    match store {
+        SecretStore::Plaintext => None,
+        SecretStore::Keyring => Some(Box::new(KeyringBackend)),
+        SecretStore::EncryptedFile => Some(Box::new(EncryptedFileBackend)),
+    }
+}
+
+struct KeyringBackend;
+
+impl KeyringBackend {
+    fn entry(url: &str) -> Result<keyring::Entry, SecretError> {
+        keyring::Entry::new(KEYRING_SERVICE, url)
+            .into_report()
+            .change_context(SecretError::KeyringError)
+    }
+
+    // A missing entry still means the keyring answered
+    fn is_available() -> bool {
+        KeyringBackend::entry(KEYRING_PROBE)
+            .is_ok_and(|entry| matches!(entry.get_password(), Ok(_) | Err(keyring::Error::NoEntry)))
+    }
+}
+
+impl SecretBackend for KeyringBackend {
+    fn get(&self, url: &str) -> Result<String, SecretError> {
+        KeyringBackend::entry(url)?
+            .get_password()
+            .map_err(|error| match error {
+                keyring::Error::NoEntry => {
+                    Report::new(SecretError::SecretNotFound(url.to_string()))
+                }
+                error => Report::new(error).change_context(SecretError::KeyringError),
+            })
+    }
+
+    fn set(&self, url: &str, secret: &str) -> Result<(), SecretError> {
+        KeyringBackend::entry(url)?
+            .set_password(secret)
+            .into_report()
+            .change_context(SecretError::KeyringError)
+    }
+
+    fn delete(&self, url: &str) -> Result<(), SecretError> {
+        match KeyringBackend::entry(url)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(error) => Err(Report::new(error).change_context(SecretError::KeyringError)),
+        }
+    }
+}
+
+// age file encrypted with a passphrase, holding the secrets of every URL as a
+// JSON object
+struct EncryptedFileBackend;
+
+// Passphrase asked once per run, from GBC_PASSPHRASE or from the terminal
+// where it is typed twice for a new file
+fn passphrase(new_file: bool) -> Secret<String> {
+    static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+    Secret::new(
+        PASSPHRASE
+            .get_or_init(|| {
+                std::env::var(PASSPHRASE_VARIABLE).unwrap_or_else(|_| {
+                    if new_file {
+                        crate::ui::get_new_passphrase()
+                    } else {
+                        crate::ui::get_passphrase()
+                    }
+                })
+            })
+            .clone(),
+    )
+}
+
+impl EncryptedFileBackend {
+    fn path() -> Result<PathBuf, SecretError> {
+        dirs::config_dir()
+            .map(|path| path.join("gbc/secrets.age"))
+            .ok_or_else(|| Report::new(SecretError::FileError))
+    }
+
+    fn read() -> Result<BTreeMap<String, String>, SecretError> {
+        let path = EncryptedFileBackend::path()?;
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let encrypted = std::fs::read(&path)
+            .into_report()
+            .change_context(SecretError::FileError)
+            .attach_printable_lazy(|| format!("Could not read {}", path.display()))?;
+
+        let decryptor = match age::Decryptor::new(&encrypted[..]) {
+            Ok(age::Decryptor::Passphrase(decryptor)) => decryptor,
+            _ => {
+                return Err(Report::new(SecretError::DecryptionError)
+                    .attach_printable(format!("{} is not passphrase encrypted", path.display())))
+            }
+        };
+        let mut decrypted = Vec::new();
+        decryptor
+            .decrypt(&passphrase(false), None)
+            .into_report()
+            .change_context(SecretError::DecryptionError)?
+            .read_to_end(&mut decrypted)
+            .into_report()
+            .change_context(SecretError::DecryptionError)?;
+
+        serde_json::from_slice(&decrypted)
+            .into_report()
+            .change_context(SecretError::FileError)
+    }
+
+    fn write(secrets: &BTreeMap<String, String>) -> Result<(), SecretError> {
+        let path = EncryptedFileBackend::path()?;
+        let plaintext = serde_json::to_vec(secrets)
+            .into_report()
+            .change_context(SecretError::EncryptionError)?;
+
+        let mut encrypted = Vec::new();
+        let mut writer = age::Encryptor::with_user_passphrase(passphrase(!path.exists()))
+            .wrap_output(&mut encrypted)
+            .into_report()
+            .change_context(SecretError::EncryptionError)?;
+        writer
+            .write_all(&plaintext)
+            .and_then(|_| writer.finish())
+            .into_report()
+            .change_context(SecretError::EncryptionError)?;
+
+        std::fs::write(&path, encrypted)
+            .into_report()
+            .change_context(SecretError::FileError)
+            .attach_printable_lazy(|| format!("Could not write {}", path.display()))
+    }
+}
+
+impl SecretBackend for EncryptedFileBackend {
+    fn get(&self, url: &str) -> Result<String, SecretError> {
+        EncryptedFileBackend::read()?
+            .remove(url)
+            .ok_or_else(|| Report::new(SecretError::SecretNotFound(url.to_string())))
+    }
+
+    fn set(&self, url: &str, secret: &str) -> Result<(), SecretError> {
+        let mut secrets = EncryptedFileBackend::read()?;
+        secrets.insert(url.to_string(), secret.to_string());
+
+        EncryptedFileBackend::write(&secrets)
+    }
+
+    fn delete(&self, url: &str) -> Result<(), SecretError> {
+        let mut secrets = EncryptedFileBackend::read()?;
+        if secrets.remove(url).is_none() {
+            return Ok(());
+        }
+
+        EncryptedFileBackend::write(&secrets)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+    use tempfile::TempDir;
+
+    // Passphrase of every test, it is kept for the whole test run
+    pub const PASSPHRASE: &str = "correct horse battery staple";
+
+    // Configuration directory in a temporary directory, the tests using it run
+    // one at a time as the environment is shared
+    pub struct ConfigHome {
+        pub dir: TempDir,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl ConfigHome {
+        pub fn new() -> ConfigHome {
+            static LOCK: Mutex<()> = Mutex::new(());
+            let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let dir = TempDir::new().unwrap();
+            std::fs::create_dir(dir.path().join("gbc")).unwrap();
+            std::env::set_var("XDG_CONFIG_HOME", dir.path());
+            std::env::set_var(PASSPHRASE_VARIABLE, PASSPHRASE);
+
+            ConfigHome { dir, _lock: lock }
+        }
+
+        pub fn path(&self, file: &str) -> PathBuf {
+            self.dir.path().join("gbc").join(file)
+        }
+    }
+
+    #[test]
+    fn encrypted_file_round_trip() {
+        let home = ConfigHome::new();
+        let backend = EncryptedFileBackend;
+
+        backend.set("https://api.github.com", "hunter2").unwrap();
+        backend.set("https://gitlab.com", "swordfish").unwrap();
+
+        assert!(!std::fs::read(home.path("secrets.age"))
+            .unwrap()
+            .windows(7)
+            .any(|window| window == b"hunter2"));
+        assert_eq!(backend.get("https://api.github.com").unwrap(), "hunter2");
+
+        backend.delete("https://api.github.com").unwrap();
+        backend.delete("https://example.com").unwrap();
+
+        assert!(matches!(
+            backend.get("https://api.github.com").unwrap_err().current_context(),
+            SecretError::SecretNotFound(url) if url == "https://api.github.com"
+        ));
+        assert_eq!(backend.get("https://gitlab.com").unwrap(), "swordfish");
+    }
+
+    #[test]
+    fn encrypted_file_rejects_a_wrong_passphrase() {
+        let home = ConfigHome::new();
+        // Encrypted with another passphrase than the one of the run
+        let mut encrypted = Vec::new();
+        let mut writer =
+            age::Encryptor::with_user_passphrase(Secret::new(String::from("wrong passphrase")))
+                .wrap_output(&mut encrypted)
+                .unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.finish().unwrap();
+        std::fs::write(home.path("secrets.age"), encrypted).unwrap();
+
+        let error = EncryptedFileBackend
+            .get("https://api.github.com")
+            .unwrap_err();
+
+        assert!(matches!(
+            error.current_context(),
+            SecretError::DecryptionError
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+// Git ref safe slugs out of the issue titles, and branch name validation
+// against git's ref-format rules
+
+use deunicode::deunicode_char;
+use error_stack::{Report, Result};
+use std::fmt;
+use std::{error::Error, fmt::Display};
+
+// Errors that can occur when checking a branch name
+#[derive(Debug)]
+pub enum SlugError {
+    InvalidBranchName(String),
+}
+
+impl Display for SlugError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlugError::InvalidBranchName(name) => write!(
+                fmt,
+                "\"{}\" is not a valid git branch name, use the slug filter in the branch formatter",
+                name
+            ),
+        }
+    }
+}
+
+impl Error for SlugError {}
+
+// Transliterate the value to ASCII and keep lower case letters and digits,
+// everything else (forbidden ref characters, punctuation, emoji...) becomes a
+// single dash. Long slugs are cut on a word boundary.
+pub fn slugify(value: &str, max_length: Option<usize>) -> String {
+    let ascii: String = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                deunicode_char(c).unwrap_or("-")
+            } else {
+                "-"
+            }
+        })
+        .collect();
+    let slug = ascii
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    match max_length {
+        Some(max_length) if slug.len() > max_length => truncate(&slug, max_length),
+        _ => slug,
+    }
+}
+
+// Cut on the last dash that fits, or in the middle of the word when the first
+// one is already too long
+fn truncate(slug: &str, max_length: usize) -> String {
+    let cut = &slug[..max_length];
+    if slug.as_bytes()[max_length] == b'-' {
+        return cut.to_string();
+    }
+
+    match cut.rfind('-') {
+        Some(index) if index > 0 => cut[..index].to_string(),
+        _ => cut.to_string(),
+    }
+}
+
+// Check the branch name with git's ref-format rules
+pub fn validate_branch_name(name: &str) -> Result<(), SlugError> {
+    match git2::Branch::name_is_valid(name) {
+        Ok(true) => Ok(()),
+        _ => Err(Report::new(SlugError::InvalidBranchName(name.to_string()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_lowercases_and_joins_words_with_dashes() {
+        assert_eq!(slugify("Fix the Login page", None), "fix-the-login-page");
+    }
+
+    #[test]
+    fn slugify_transliterates_unicode() {
+        assert_eq!(slugify("Café crème à l'été", None), "cafe-creme-a-l-ete");
+        assert_eq!(slugify("Straße Ærø", None), "strasse-aero");
+        assert_eq!(slugify("北京", None), "bei-jing");
+    }
+
+    #[test]
+    fn slugify_collapses_forbidden_characters() {
+        assert_eq!(
+            slugify("  Crash on ~user^/..lock: why?* [urgent] 🔥 ", None),
+            "crash-on-user-lock-why-urgent"
+        );
+    }
+
+    #[test]
+    fn slugify_keeps_no_dots() {
+        assert_eq!(slugify("config.lock", None), "config-lock");
+        assert_eq!(slugify("a..b", None), "a-b");
+    }
+
+    #[test]
+    fn slugify_of_punctuation_only_is_empty() {
+        assert_eq!(slugify("?!... 🔥", None), "");
+    }
+
+    #[test]
+    fn truncate_cuts_on_a_word_boundary() {
+        assert_eq!(slugify("fix the login page", Some(12)), "fix-the");
+        assert_eq!(slugify("fix the login page", Some(13)), "fix-the-login");
+        assert_eq!(slugify("fix the login page", Some(7)), "fix-the");
+    }
+
+    #[test]
+    fn truncate_cuts_inside_a_too_long_first_word() {
+        assert_eq!(slugify("internationalization issue", Some(5)), "inter");
+    }
+
+    #[test]
+    fn truncate_leaves_short_slugs() {
+        assert_eq!(slugify("short", Some(30)), "short");
+        assert_eq!(slugify("exact", Some(5)), "exact");
+    }
+
+    #[test]
+    fn validate_branch_name_follows_git_rules() {
+        assert!(validate_branch_name("feature/42-fix-login").is_ok());
+        assert!(validate_branch_name("feature/a..b").is_err());
+        assert!(validate_branch_name("feature/branch.lock").is_err());
+        assert!(validate_branch_name("feature/with space").is_err());
+        assert!(validate_branch_name("feature/").is_err());
+        assert!(validate_branch_name("feature/~tilde").is_err());
+    }
+}
@@ -0,0 +1,453 @@
+// Branch name template engine used by the `formatter` of the branch kinds.
+//
+// Placeholders are written between braces with optional filters separated by
+// pipes, `{title|slug|truncate(30)}`, and braces are escaped by doubling them.
+
+use error_stack::{Report, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::{error::Error, fmt::Display};
+
+use crate::drivers::issue::Issue;
+use crate::slug::slugify;
+
+// Placeholders available besides the issue fields
+const EXTRA_PLACEHOLDERS: [&str; 5] = ["name", "user", "date", "kind", "repo"];
+
+// Errors that can occur when parsing a template
+#[derive(Debug)]
+pub enum TemplateError {
+    UnclosedPlaceholder,
+    UnexpectedBrace,
+    UnknownPlaceholder(String),
+    UnknownFilter(String),
+    InvalidFilterArgument(String),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnclosedPlaceholder => fmt.write_str("Placeholder not closed"),
+            TemplateError::UnexpectedBrace => {
+                fmt.write_str("Unexpected '}', use '}}' for a literal brace")
+            }
+            TemplateError::UnknownPlaceholder(name) => write!(
+                fmt,
+                "Unknown placeholder {{{}}}, available ones are {}",
+                name,
+                placeholders().join(", ")
+            ),
+            TemplateError::UnknownFilter(name) => write!(
+                fmt,
+                "Unknown filter {}, available ones are lower, upper, slug, truncate(n) and default(value)",
+                name
+            ),
+            TemplateError::InvalidFilterArgument(filter) => {
+                write!(fmt, "Invalid argument for the filter {}", filter)
+            }
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+// Every placeholder name known by the engine
+fn placeholders() -> Vec<&'static str> {
+    Issue::FIELDS
+        .into_iter()
+        .chain(EXTRA_PLACEHOLDERS)
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Lower,
+    Upper,
+    Slug,
+    Truncate(usize),
+    Default(String),
+}
+
+impl Filter {
+    fn parse(source: &str) -> Result<Filter, TemplateError> {
+        let source = source.trim();
+        let (name, argument) = match source.split_once('(') {
+            Some((name, rest)) => {
+                let argument = rest.strip_suffix(')').ok_or_else(|| {
+                    Report::new(TemplateError::InvalidFilterArgument(source.to_string()))
+                })?;
+                (name.trim(), Some(argument.trim()))
+            }
+            None => (source, None),
+        };
+
+        match (name, argument) {
+            ("lower", None) => Ok(Filter::Lower),
+            ("upper", None) => Ok(Filter::Upper),
+            ("slug", None) => Ok(Filter::Slug),
+            ("truncate", Some(length)) => length
+                .parse()
+                .map(Filter::Truncate)
+                .map_err(|_| Report::new(TemplateError::InvalidFilterArgument(source.to_string()))),
+            ("default", Some(value)) => Ok(Filter::Default(
+                value.trim_matches(|c| c == '"' || c == '\'').to_string(),
+            )),
+            ("lower" | "upper" | "slug" | "truncate" | "default", _) => Err(Report::new(
+                TemplateError::InvalidFilterArgument(source.to_string()),
+            )),
+            _ => Err(Report::new(TemplateError::UnknownFilter(name.to_string()))),
+        }
+    }
+
+    fn apply(&self, value: String, context: &Context) -> String {
+        match self {
+            Filter::Lower => value.to_lowercase(),
+            Filter::Upper => value.to_uppercase(),
+            Filter::Slug => slugify(&value, context.slug_max_length),
+            Filter::Truncate(length) => value.chars().take(*length).collect(),
+            Filter::Default(default) if value.is_empty() => default.clone(),
+            Filter::Default(_) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Placeholder { name: String, filters: Vec<Filter> },
+}
+
+// Parsed template, ready to be rendered
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(Report::new(TemplateError::UnexpectedBrace)),
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(Report::new(TemplateError::UnclosedPlaceholder)),
+                        }
+                    }
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Template::parse_placeholder(&placeholder)?);
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Template { parts })
+    }
+
+    fn parse_placeholder(source: &str) -> Result<Part, TemplateError> {
+        let mut sections = source.split('|');
+        let name = sections.next().unwrap_or_default().trim().to_string();
+        if !placeholders().contains(&name.as_str()) {
+            return Err(Report::new(TemplateError::UnknownPlaceholder(name)));
+        }
+        let filters = sections.map(Filter::parse).collect::<Result<_, _>>()?;
+
+        Ok(Part::Placeholder { name, filters })
+    }
+
+    // Render the template, missing values are rendered as empty strings
+    pub fn render(&self, context: &Context) -> String {
+        self.parts
+            .iter()
+            .map(|part| Template::render_part(part, context))
+            .collect()
+    }
+
+    fn render_part(part: &Part, context: &Context) -> String {
+        match part {
+            Part::Text(text) => text.clone(),
+            Part::Placeholder { name, filters } => {
+                filters.iter().fold(context.value(name), |value, filter| {
+                    filter.apply(value, context)
+                })
+            }
+        }
+    }
+
+    // Names the template gives to the issue whatever its title, the user or
+    // the date: only the text and the id are kept, the other placeholders
+    // match anything. None when the template does not use the id.
+    pub fn pattern(&self, context: &Context) -> Option<Pattern> {
+        let mut parts = Vec::new();
+        let mut has_id = false;
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => parts.push(Some(text.clone())),
+                Part::Placeholder { name, .. } if name == "id" => {
+                    has_id = true;
+                    parts.push(Some(Template::render_part(part, context)));
+                }
+                Part::Placeholder { .. } => {
+                    if parts.last() != Some(&None) {
+                        parts.push(None);
+                    }
+                }
+            }
+        }
+
+        has_id.then_some(Pattern { parts })
+    }
+}
+
+// Branch name pattern, None parts are wildcards
+#[derive(Debug)]
+pub struct Pattern {
+    parts: Vec<Option<String>>,
+}
+
+impl Pattern {
+    // Numbered variants, feature/42-title-2, match as well
+    pub fn matches(&self, name: &str) -> bool {
+        let unsuffixed = name
+            .rsplit_once('-')
+            .filter(|(_, suffix)| !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()))
+            .map(|(name, _)| name);
+
+        matches_parts(&self.parts, name)
+            || unsuffixed.is_some_and(|name| matches_parts(&self.parts, name))
+    }
+}
+
+fn matches_parts(parts: &[Option<String>], name: &str) -> bool {
+    match parts.split_first() {
+        None => name.is_empty(),
+        Some((Some(text), rest)) => name
+            .strip_prefix(text.as_str())
+            .is_some_and(|name| matches_parts(rest, name)),
+        Some((None, rest)) => name
+            .char_indices()
+            .map(|(index, _)| index)
+            .chain([name.len()])
+            .any(|index| matches_parts(rest, &name[index..])),
+    }
+}
+
+// Values available to the templates
+pub struct Context<'a> {
+    issue: &'a Issue,
+    values: HashMap<&'static str, String>,
+    slug_max_length: Option<usize>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(issue: &'a Issue, slug_max_length: Option<usize>) -> Context<'a> {
+        let values = HashMap::from([
+            ("name", slugify(&issue.title, slug_max_length)),
+            ("date", chrono::Local::now().format("%Y-%m-%d").to_string()),
+        ]);
+
+        Context {
+            issue,
+            values,
+            slug_max_length,
+        }
+    }
+
+    // Set one of the placeholders that do not come from the issue
+    pub fn with(mut self, name: &'static str, value: Option<String>) -> Context<'a> {
+        self.values.insert(name, value.unwrap_or_default());
+        self
+    }
+
+    fn value(&self, name: &str) -> String {
+        self.values
+            .get(name)
+            .cloned()
+            .or_else(|| self.issue.field(name))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue() -> Issue {
+        Issue {
+            id: String::from("ABC-42"),
+            title: String::from("Fix the Login page"),
+            priority: Some(String::from("High")),
+            ..Default::default()
+        }
+    }
+
+    fn render(source: &str) -> String {
+        let issue = issue();
+        let context = Context::new(&issue, Some(10)).with("kind", Some(String::from("feature")));
+
+        Template::parse(source).unwrap().render(&context)
+    }
+
+    fn error(source: &str) -> Report<TemplateError> {
+        match Template::parse(source) {
+            Ok(_) => panic!("{} should not parse", source),
+            Err(report) => report,
+        }
+    }
+
+    #[test]
+    fn renders_issue_fields_and_extra_placeholders() {
+        assert_eq!(render("{kind}/{id}-{name}"), "feature/ABC-42-fix-the");
+        assert_eq!(render("{id}"), "ABC-42");
+    }
+
+    #[test]
+    fn renders_missing_values_as_empty() {
+        assert_eq!(render("{kind}/{assignee}{id}"), "feature/ABC-42");
+        assert_eq!(render("{user}-{id}"), "-ABC-42");
+    }
+
+    #[test]
+    fn escapes_doubled_braces() {
+        assert_eq!(render("{{{id}}}"), "{ABC-42}");
+        assert_eq!(render("a}}b{{c"), "a}b{c");
+    }
+
+    #[test]
+    fn applies_filters_in_order() {
+        assert_eq!(render("{id|lower}"), "abc-42");
+        assert_eq!(render("{title|upper}"), "FIX THE LOGIN PAGE");
+        assert_eq!(render("{title|slug}"), "fix-the");
+        assert_eq!(render("{title|truncate(7)|slug}"), "fix-the");
+        assert_eq!(render("{title | lower | truncate( 3 )}"), "fix");
+    }
+
+    #[test]
+    fn default_filter_only_replaces_empty_values() {
+        assert_eq!(render("{assignee|default(nobody)}"), "nobody");
+        assert_eq!(render("{assignee|default(\"no one\")}"), "no one");
+        assert_eq!(render("{priority|default('none')|lower}"), "high");
+    }
+
+    #[test]
+    fn rejects_unclosed_placeholders() {
+        assert!(matches!(
+            error("{id").current_context(),
+            TemplateError::UnclosedPlaceholder
+        ));
+        assert!(matches!(
+            error("feature/{id|slug").current_context(),
+            TemplateError::UnclosedPlaceholder
+        ));
+    }
+
+    #[test]
+    fn rejects_unexpected_closing_braces() {
+        assert!(matches!(
+            error("id}").current_context(),
+            TemplateError::UnexpectedBrace
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert!(
+            matches!(error("{nope}").current_context(), TemplateError::UnknownPlaceholder(name) if name == "nope")
+        );
+        assert!(
+            matches!(error("{}").current_context(), TemplateError::UnknownPlaceholder(name) if name.is_empty())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_filters() {
+        assert!(
+            matches!(error("{id|reverse}").current_context(), TemplateError::UnknownFilter(name) if name == "reverse")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_filter_arguments() {
+        for source in [
+            "{id|truncate}",
+            "{id|truncate(x)}",
+            "{id|truncate(-1)}",
+            "{id|truncate(3}",
+            "{id|default}",
+            "{id|lower(1)}",
+        ] {
+            assert!(
+                matches!(
+                    error(source).current_context(),
+                    TemplateError::InvalidFilterArgument(_)
+                ),
+                "{}",
+                source
+            );
+        }
+    }
+
+    fn pattern(source: &str) -> Option<Pattern> {
+        let issue = issue();
+        let context = Context::new(&issue, Some(10)).with("kind", Some(String::from("feature")));
+
+        Template::parse(source).unwrap().pattern(&context)
+    }
+
+    #[test]
+    fn pattern_matches_other_titles_of_the_issue() {
+        let pattern = pattern("{kind}/{id}-{name}").unwrap();
+
+        assert!(pattern.matches("feature/ABC-42-fix-the"));
+        assert!(pattern.matches("feature/ABC-42-old-title"));
+        assert!(pattern.matches("bug/ABC-42-old-title"));
+        assert!(!pattern.matches("feature/ABC-421-old-title"));
+        assert!(!pattern.matches("feature/ABC-4-old-title"));
+    }
+
+    #[test]
+    fn pattern_matches_the_id_after_the_slug() {
+        let pattern = pattern("{kind}/{name}-{id}").unwrap();
+
+        assert!(pattern.matches("feature/old-title-ABC-42"));
+        assert!(pattern.matches("feature/old-title-ABC-42-2"));
+        assert!(!pattern.matches("feature/old-title-ABC-421"));
+        assert!(!pattern.matches("feature/old-title-XABC-42"));
+    }
+
+    #[test]
+    fn pattern_ignores_the_user_and_the_date() {
+        let pattern = pattern("{user}/{date}/{id|lower}").unwrap();
+
+        assert!(pattern.matches("jdoe/2024-01-31/abc-42"));
+        assert!(pattern.matches("/2025-06-01/abc-42"));
+        assert!(!pattern.matches("jdoe/2024-01-31/ABC-42"));
+    }
+
+    #[test]
+    fn pattern_needs_the_id() {
+        assert!(pattern("{kind}/{name}").is_none());
+    }
+}
@@ -1,38 +1,109 @@
 // CLI UI components
 pub mod fuzzy_finder;
 
+use crate::drivers::Driver;
 use crate::features::{
     global_config::{Access, Credentials},
     init::ProjectConfig,
 };
+use crate::git::ExistingBranchAction;
+use crate::secrets::SecretStore;
 
-fn prompt(question: &str) -> String {
+fn read_answer() -> String {
     let mut answer = String::new();
-    println!("{}", question);
     std::io::stdin().read_line(&mut answer).unwrap_or_default();
     answer.trim().to_string()
 }
 
+fn prompt(question: &str) -> String {
+    println!("{}", question);
+    read_answer()
+}
+
+// Same as prompt without echoing the answer, for the passwords and tokens.
+// Without a terminal the answer is read from the standard input.
+fn prompt_secret(question: &str) -> String {
+    println!("{}", question);
+    rpassword::read_password()
+        .map(|answer| answer.trim().to_string())
+        .unwrap_or_else(|_| read_answer())
+}
+
 // Helper function that creates a new Credentials struct from the user input
 // and updates the Configurations file with the new credentials
 pub fn get_credentials() -> Credentials {
     let url = prompt("Enter the URL of the API");
-    let access = prompt("Enter the access type (basic or token)");
+    let access = prompt("Enter the access type (basic, token, env, command or file)");
 
-    match access.as_str() {
+    Credentials {
+        url,
+        access: get_access(&access),
+        store: SecretStore::default(),
+    }
+}
+
+// Prompt new values for existing credentials, the empty answers keep the
+// current URL and access
+pub fn edit_credentials(current: Credentials) -> Credentials {
+    let url = prompt(&format!(
+        "Enter the URL of the API, empty to keep {}",
+        current.url
+    ));
+    let access = prompt(&format!(
+        "Enter the access type (basic, token, env, command or file), empty to keep the current {} access",
+        current.access.kind()
+    ));
+
+    Credentials {
+        url: if url.is_empty() { current.url } else { url },
+        access: if access.is_empty() {
+            current.access
+        } else {
+            get_access(&access)
+        },
+        store: current.store,
+    }
+}
+
+// Prompt the details of an access type
+fn get_access(access: &str) -> Access {
+    match access {
         "basic" => {
             let username = prompt("Enter the username");
-            let password = prompt("Enter the password");
-            Credentials {
-                url,
-                access: Access::Basic { username, password },
-            }
+            let password = prompt_secret("Enter the password");
+            Access::Basic { username, password }
         }
         "token" => {
-            let token = prompt("Enter the token");
-            Credentials {
-                url,
-                access: Access::Token { token },
+            let token = prompt_secret("Enter the token");
+            Access::Token { token }
+        }
+        "env" => {
+            let variable = prompt("Enter the environment variable holding the secret");
+            Access::Env {
+                variable,
+                username: get_optional_username(),
+            }
+        }
+        "command" => {
+            let command_line = prompt("Enter the command printing the secret");
+            // Split like a shell, the quoted `op read "op://vault/jira token"` path
+            // stays a single argument
+            let Some(mut words) = shlex::split(&command_line).filter(|words| !words.is_empty())
+            else {
+                println!("Invalid command, check its quotes");
+                std::process::exit(1);
+            };
+            Access::Command {
+                command: words.remove(0),
+                args: words,
+                username: get_optional_username(),
+            }
+        }
+        "file" => {
+            let path = prompt("Enter the path of the file holding the secret");
+            Access::File {
+                path: path.into(),
+                username: get_optional_username(),
             }
         }
         _ => {
@@ -42,10 +113,71 @@ pub fn get_credentials() -> Credentials {
     }
 }
 
+// Username of the secrets read from an external source, the secret is a
+// token without it
+fn get_optional_username() -> Option<String> {
+    Some(prompt(
+        "Enter the username, empty when the secret is a token",
+    ))
+    .filter(|username| !username.is_empty())
+}
+
+// Client secret of the OAuth application used by `gbc login jira`
+pub fn get_client_secret() -> String {
+    prompt_secret("Enter the client secret of the OAuth application")
+}
+
+// Passphrase of the encrypted secrets file
+pub fn get_passphrase() -> String {
+    prompt_secret("Enter the passphrase of the gbc secrets")
+}
+
+// Passphrase of a new encrypted secrets file, typed twice
+pub fn get_new_passphrase() -> String {
+    let passphrase = prompt_secret("Choose a passphrase for the gbc secrets");
+    if passphrase.is_empty() {
+        println!("The passphrase can not be empty");
+        std::process::exit(1);
+    }
+    if prompt_secret("Enter the passphrase again") != passphrase {
+        println!("The passphrases do not match");
+        std::process::exit(1);
+    }
+
+    passphrase
+}
+
 // Helper function that initialize the project configuration file from the user input
 pub fn get_project_config() -> ProjectConfig {
     let identifier = prompt("Enter the project/board identifier");
     let url = prompt("Enter the project API URL");
+    let driver = prompt("Enter the issue tracker driver (mock, jira, github, gitlab, notion, linear, file, command)");
+
+    match driver.parse::<Driver>() {
+        Ok(driver) => ProjectConfig::new(identifier, url, driver, None),
+        Err(_) => {
+            println!("Invalid driver");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Helper function that asks what to do with the branches that already exist
+// for the issue
+pub fn get_existing_branch_action(branches: &[String]) -> ExistingBranchAction {
+    println!("The issue already has a branch:");
+    branches.iter().for_each(|branch| println!("  {}", branch));
+    loop {
+        let action = prompt(&format!(
+            "[c]heckout {}, create a [s]uffixed branch or [a]bort?",
+            branches[0]
+        ));
 
-    ProjectConfig::new(identifier, url, None)
+        match action.to_lowercase().as_str() {
+            "c" | "checkout" => return ExistingBranchAction::Checkout,
+            "s" | "suffix" => return ExistingBranchAction::Suffix,
+            "a" | "abort" => return ExistingBranchAction::Abort,
+            _ => continue,
+        }
+    }
 }
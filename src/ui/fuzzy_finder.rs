@@ -15,18 +15,41 @@ pub enum FuzzyFinderError {
 
 impl Display for FuzzyFinderError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.write_str("Credit card error: Could not retrieve credit card.")
+        match self {
+            FuzzyFinderError::Empty => fmt.write_str("No issue selected"),
+            FuzzyFinderError::Other(e) => write!(fmt, "Fuzzy finder error: {}", e),
+        }
     }
 }
 
 impl Error for FuzzyFinderError {}
 
+// Line displayed for an issue with the metadata known by the driver
+pub fn label(issue: &Issue) -> String {
+    let mut label = format!("{} {}", issue.id, issue.title);
+    let details: Vec<&str> = [&issue.issue_type, &issue.priority, &issue.status]
+        .into_iter()
+        .filter_map(|detail| detail.as_deref())
+        .collect();
+    if !details.is_empty() {
+        label.push_str(&format!(" [{}]", details.join(", ")));
+    }
+    if let Some(assignee) = &issue.assignee {
+        label.push_str(&format!(" @{}", assignee));
+    }
+    for issue_label in &issue.labels {
+        label.push_str(&format!(" #{}", issue_label));
+    }
+
+    label
+}
+
 // Render a fuzzy_finder interface and returs a result or an error.
 pub fn render(issues: Vec<Issue>) -> Result<Issue, FuzzyFinderError> {
     // Create a list of items from the list of issues
     let items = issues
         .iter()
-        .map(|issue| Item::new(issue.id.to_string(), issue.title.to_string()))
+        .map(|issue| Item::new(label(issue), issue.id.to_string()))
         .collect();
 
     // Prompt the user to select an issue from the list of issues